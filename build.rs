@@ -0,0 +1,92 @@
+// Generates the static flag registries from the CSVs in data/, see src/consts/countries.rs
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+// (CSV file, name of the generated static)
+const DATASETS: [(&str, &str); 3] = [
+    ("data/countries.csv", "COUNTRIES"),
+    ("data/subdivisions.csv", "SUBDIVISIONS"),
+    ("data/organizations.csv", "ORGANIZATIONS"),
+];
+const REGIONS: [&str; 7] = [
+    "Africa",
+    "Americas",
+    "Asia",
+    "Europe",
+    "Oceania",
+    "Antarctica",
+    "International",
+];
+const STATUSES: [(&str, &str); 4] = [
+    ("sovereign", "Sovereign"),
+    ("territory", "Territory"),
+    ("subdivision", "Subdivision"),
+    ("organization", "Organization"),
+];
+
+fn main() {
+    let mut out = String::new();
+    for (path, name) in DATASETS {
+        println!("cargo:rerun-if-changed={}", path);
+        generate(path, name, &mut out);
+    }
+
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("countries.rs");
+    fs::write(dest, out).unwrap();
+}
+
+fn generate(path: &str, name: &str, out: &mut String) {
+    let mut reader = csv::Reader::from_path(path)
+        .unwrap_or_else(|err| panic!("failed to open {}: {}", path, err));
+    let mut rows: Vec<csv::StringRecord> = reader
+        .records()
+        .map(|record| record.unwrap_or_else(|err| panic!("failed to read {}: {}", path, err)))
+        .collect();
+    rows.sort_by(|a, b| a[0].cmp(&b[0])); // sorted by code so lookups can binary search
+
+    writeln!(out, "pub static {}: &[Country] = &[", name).unwrap();
+    for row in &rows {
+        let line = row.position().map_or(0, |position| position.line());
+        let [code, alpha3, numeric, name, emoji, region, status, un_member] =
+            [0, 1, 2, 3, 4, 5, 6, 7].map(|column| &row[column]);
+
+        assert!(
+            REGIONS.contains(&region),
+            "{}:{}: unknown region '{}'",
+            path,
+            line,
+            region
+        );
+        let status = STATUSES
+            .iter()
+            .find(|(key, _)| *key == status)
+            .unwrap_or_else(|| panic!("{}:{}: unknown status '{}'", path, line, status))
+            .1;
+        let un_member: bool = un_member
+            .parse()
+            .unwrap_or_else(|_| panic!("{}:{}: un_member must be true or false", path, line));
+        let alpha3 = match alpha3 {
+            "" => "None".to_string(),
+            alpha3 => format!("Some({:?})", alpha3),
+        };
+        let numeric = match numeric {
+            "" => "None".to_string(),
+            numeric => format!(
+                "Some({})",
+                numeric
+                    .parse::<u16>()
+                    .unwrap_or_else(|_| panic!("{}:{}: bad numeric code", path, line))
+            ),
+        };
+
+        writeln!(
+            out,
+            "    Country {{ code: {:?}, alpha3: {}, numeric: {}, name: {:?}, emoji: {:?}, region: Region::{}, status: Status::{}, un_member: {} }},",
+            code, alpha3, numeric, name, emoji, region, status, un_member
+        )
+        .unwrap();
+    }
+    out.push_str("];\n");
+}
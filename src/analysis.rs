@@ -0,0 +1,222 @@
+use futures::stream::{self, Stream, StreamExt};
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::fetch_flags::{load_flag_file, FetchError, FlagFetcher};
+use crate::flags::{FlagEntry, FlagSource};
+use crate::palette::{palette, PaletteColor};
+use crate::symmetry::{check_symmetry, symmetry_scores, Symmetry, SymmetryScores};
+use crate::url_template::redact_url;
+use crate::vector_symmetry::check_svg_symmetry;
+
+/// The category a flag is reported under. Rotational symmetry doesn't get its own category.
+/// Serialized with the short labels used in results.csv.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SymmetryClass {
+    Full,
+    #[serde(rename = "Horz")]
+    Horizontal,
+    #[serde(rename = "Vert")]
+    Vertical,
+    None,
+}
+
+impl SymmetryClass {
+    pub const ALL: [SymmetryClass; 4] = [
+        SymmetryClass::Full,
+        SymmetryClass::Horizontal,
+        SymmetryClass::Vertical,
+        SymmetryClass::None,
+    ];
+
+    /// ```
+    /// use flag_symmetry::{Symmetry, SymmetryClass};
+    ///
+    /// let france = Symmetry { horizontal: true, vertical: false, rotational: false };
+    /// assert_eq!(SymmetryClass::of(&france), SymmetryClass::Horizontal);
+    /// ```
+    pub fn of(symmetry: &Symmetry) -> SymmetryClass {
+        match (symmetry.horizontal, symmetry.vertical) {
+            (true, true) => SymmetryClass::Full,
+            (true, false) => SymmetryClass::Horizontal,
+            (false, true) => SymmetryClass::Vertical,
+            (false, false) => SymmetryClass::None,
+        }
+    }
+
+    /// Short label used in results.csv.
+    pub fn label(self) -> &'static str {
+        match self {
+            SymmetryClass::Full => "Full",
+            SymmetryClass::Horizontal => "Horz",
+            SymmetryClass::Vertical => "Vert",
+            SymmetryClass::None => "None",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AnalysisError {
+    Fetch(FetchError),
+    Svg(usvg::Error),
+    Cancelled, // the runtime shut down before the analysis finished
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalysisError::Fetch(err) => write!(f, "{}", err),
+            AnalysisError::Svg(err) => write!(f, "failed to parse SVG: {}", err),
+            AnalysisError::Cancelled => write!(f, "analysis was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for AnalysisError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AnalysisError::Fetch(err) => Some(err),
+            AnalysisError::Svg(err) => Some(err),
+            AnalysisError::Cancelled => None,
+        }
+    }
+}
+
+impl From<FetchError> for AnalysisError {
+    fn from(err: FetchError) -> Self {
+        AnalysisError::Fetch(err)
+    }
+}
+
+impl From<usvg::Error> for AnalysisError {
+    fn from(err: usvg::Error) -> Self {
+        AnalysisError::Svg(err)
+    }
+}
+
+const PALETTE_SIZE: usize = 5;
+
+/// Outcome of analysing one flag.
+#[derive(Debug)]
+pub struct FlagResult {
+    pub entry: FlagEntry,
+    pub source: String, // URL or path the image was read from
+    pub width: u32,
+    pub height: u32,
+    pub symmetry: Symmetry,
+    pub scores: SymmetryScores,
+    pub palette: Vec<PaletteColor>,
+    pub vector_symmetry: Option<Symmetry>, // only for registry flags, when the SVG was compared too
+    pub image: DynamicImage,               // the decoded raster, for reports that show the flag
+}
+
+impl FlagResult {
+    pub fn class(&self) -> SymmetryClass {
+        SymmetryClass::of(&self.symmetry)
+    }
+
+    /// Whether the SVG analysis came to a different answer than the raster one.
+    pub fn vector_mismatch(&self) -> bool {
+        self.vector_symmetry
+            .as_ref()
+            .is_some_and(|vector| *vector != self.symmetry)
+    }
+}
+
+/// Fetches and analyses `entries`, keeping up to `max_in_flight` downloads running at once.
+/// Results are yielded alongside their entry in the same order as `entries`, whichever request
+/// finishes first, so one failed flag doesn't hold up the rest.
+///
+/// ```
+/// use flag_symmetry::{analyse_flags, ClientConfig, FlagEntry, FlagFetcher};
+/// use futures::StreamExt;
+/// use image::{Rgba, RgbaImage};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let dir = tempfile::tempdir()?;
+/// let path = dir.path().join("white.png");
+/// RgbaImage::from_pixel(30, 20, Rgba([255, 255, 255, 255])).save(&path)?;
+///
+/// // local files don't touch the network, registry flags are downloaded by the fetcher
+/// let fetcher = FlagFetcher::new(ClientConfig::default().build()?, 10.0);
+/// let entries = [FlagEntry::from_path(&path)];
+/// let results: Vec<_> = analyse_flags(&fetcher, &entries, 4, false).collect().await;
+///
+/// let (_, result) = &results[0];
+/// assert!(result.as_ref().unwrap().symmetry.rotational);
+/// # Ok(())
+/// # }
+/// ```
+pub fn analyse_flags<'a>(
+    fetcher: &'a FlagFetcher,
+    entries: &'a [FlagEntry],
+    max_in_flight: usize,
+    compare_vector: bool,
+) -> impl Stream<Item = (&'a FlagEntry, Result<FlagResult, AnalysisError>)> + 'a {
+    stream::iter(entries)
+        .map(
+            move |entry| async move { (entry, analyse_flag(fetcher, entry, compare_vector).await) },
+        )
+        .buffered(max_in_flight.max(1))
+}
+
+/// The URL or path an entry's image is read from, with any credentials in the URL redacted.
+pub fn flag_source(fetcher: &FlagFetcher, entry: &FlagEntry) -> String {
+    match &entry.source {
+        FlagSource::Registry => redact_url(&fetcher.flag_url(&entry.id)),
+        FlagSource::Url(url) => redact_url(url),
+        FlagSource::Path(path) => path.display().to_string(),
+    }
+}
+
+async fn analyse_flag(
+    fetcher: &FlagFetcher,
+    entry: &FlagEntry,
+    compare_vector: bool,
+) -> Result<FlagResult, AnalysisError> {
+    // the SVG is only known for registry flags
+    let (img, svg) = match &entry.source {
+        FlagSource::Registry => {
+            let img = fetcher.get_flag(&entry.id).await;
+            let svg = if compare_vector {
+                Some(fetcher.get_flag_svg(&entry.id).await)
+            } else {
+                None
+            };
+            (img, svg)
+        }
+        FlagSource::Url(url) => (fetcher.get_flag_from_url(&entry.id, url).await, None),
+        FlagSource::Path(path) => (load_flag_file(path), None),
+    };
+
+    let img = img?;
+    let svg = svg.transpose()?;
+    let entry = entry.clone();
+    let source = flag_source(fetcher, &entry);
+
+    // the pixel work runs on the blocking pool so it doesn't stall the downloads still in flight
+    let analysis = tokio::task::spawn_blocking(move || {
+        let vector_symmetry = match svg {
+            Some(svg) => Some(check_svg_symmetry(&svg)?),
+            None => None,
+        };
+        Ok(FlagResult {
+            entry,
+            source,
+            width: img.width(),
+            height: img.height(),
+            symmetry: check_symmetry(&img),
+            scores: symmetry_scores(&img),
+            palette: palette(&img, PALETTE_SIZE),
+            vector_symmetry,
+            image: img,
+        })
+    });
+    match analysis.await {
+        Ok(result) => result,
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(_) => Err(AnalysisError::Cancelled),
+    }
+}
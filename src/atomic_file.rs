@@ -0,0 +1,185 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// tells apart temp files for the same path created by one process, e.g. concurrent cache writes
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A file that only appears at `path` once `commit` is called. Everything is written to a
+/// temporary file next to it first and renamed over `path`, so an aborted run never leaves a
+/// truncated file behind.
+///
+/// ```
+/// use flag_symmetry::atomic_file::AtomicFile;
+/// use std::io::Write;
+///
+/// let dir = tempfile::tempdir()?;
+/// let path = dir.path().join("results.csv");
+/// let mut file = AtomicFile::create(&path, false)?;
+/// file.write_all(b"code,class\n")?;
+/// assert!(!path.exists());
+///
+/// file.commit()?;
+/// assert_eq!(std::fs::read_to_string(&path)?, "code,class\n");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    file: Option<BufWriter<File>>, // taken by commit
+}
+
+impl AtomicFile {
+    /// Fails with `ErrorKind::AlreadyExists` if `path` exists and `overwrite` isn't set.
+    pub fn create(path: &Path, overwrite: bool) -> io::Result<AtomicFile> {
+        if !overwrite && path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+
+        // same directory as the target so the rename can't cross file systems
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp_path = path.with_file_name(format!(
+            ".{}.{}.{}.tmp",
+            file_name,
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = BufWriter::new(File::create(&temp_path)?);
+
+        Ok(AtomicFile {
+            path: path.to_path_buf(),
+            temp_path,
+            file: Some(file),
+        })
+    }
+
+    /// Flushes everything to disk and moves the file into place.
+    pub fn commit(mut self) -> io::Result<()> {
+        if let Some(file) = self.file.take() {
+            let file = file.into_inner().map_err(|err| err.into_error())?;
+            file.sync_all()?;
+        }
+        std::fs::rename(&self.temp_path, &self.path)
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.file {
+            Some(file) => file.write(buf),
+            None => Err(io::Error::other("file already committed")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        // never committed, e.g. the run failed part way: leave whatever was at `path` alone
+        if self.file.is_some() {
+            self.file = None;
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Where results go: a file written atomically, or stdout for `-`.
+pub enum OutputTarget {
+    Stdout(io::Stdout),
+    File(AtomicFile),
+}
+
+impl OutputTarget {
+    /// Stdout for `-`, otherwise an `AtomicFile` at `path`.
+    pub fn open(path: &Path, overwrite: bool) -> io::Result<OutputTarget> {
+        if path.as_os_str() == "-" {
+            Ok(OutputTarget::Stdout(io::stdout()))
+        } else {
+            Ok(OutputTarget::File(AtomicFile::create(path, overwrite)?))
+        }
+    }
+
+    pub fn is_stdout(&self) -> bool {
+        matches!(self, OutputTarget::Stdout(_))
+    }
+
+    /// Flushes stdout, or moves the file into place.
+    pub fn commit(self) -> io::Result<()> {
+        match self {
+            OutputTarget::Stdout(mut stdout) => stdout.flush(),
+            OutputTarget::File(file) => file.commit(),
+        }
+    }
+}
+
+impl Write for OutputTarget {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputTarget::Stdout(stdout) => stdout.write(buf),
+            OutputTarget::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputTarget::Stdout(stdout) => stdout.flush(),
+            OutputTarget::File(file) => file.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn commit_replaces_the_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("results.csv");
+        std::fs::write(&path, "old").unwrap();
+
+        let mut file = AtomicFile::create(&path, true).unwrap();
+        file.write_all(b"new").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old");
+        file.commit().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+    }
+
+    #[test]
+    fn dropping_without_commit_keeps_the_old_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("results.csv");
+        std::fs::write(&path, "old").unwrap();
+
+        let mut file = AtomicFile::create(&path, true).unwrap();
+        file.write_all(b"partial").unwrap();
+        let temp = file.temp_path.clone();
+        drop(file);
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old");
+        assert!(!temp.exists());
+    }
+
+    #[test]
+    fn refuses_to_overwrite_without_permission() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("results.csv");
+        std::fs::write(&path, "old").unwrap();
+
+        let err = AtomicFile::create(&path, false).err().unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+}
@@ -0,0 +1,258 @@
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use flag_symmetry::consts::countries::{CountryOrder, Dataset};
+use flag_symmetry::fetch_flags::{parse_rate, DEFAULT_REQUESTS_PER_SECOND};
+use flag_symmetry::locale::Lang;
+use flag_symmetry::results::OutputFormat;
+use flag_symmetry::symmetry::Axis;
+use flag_symmetry::url_template::UrlTemplate;
+
+pub const DEFAULT_CONCURRENCY: usize = 8;
+pub const DEFAULT_CACHE_DIR: &str = ".flag_cache";
+pub const DEFAULT_RESULTS_PATH: &str = "results.csv";
+pub const DEFAULT_RESULTS_STEM: &str = "results";
+
+/// Reports which of the world's flags are symmetrical.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Analyse every selected flag, write results.csv and print the summary
+    Run(RunArgs),
+    /// Analyse a single flag and print the detailed result
+    Check(CheckArgs),
+    /// Analyse local image files, such as design mock-ups, without the country registry
+    Files(FilesArgs),
+    /// Download the selected flags into the cache without analysing them
+    Fetch(FetchArgs),
+    /// Print the summary of a saved results file, or the README section with --format markdown
+    Report(ReportArgs),
+    /// List the flags whose symmetry differs between two results files
+    Diff(DiffArgs),
+}
+
+// also serialized into the metadata block of JSON results
+#[derive(Args, Serialize)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub selection: Selection,
+    #[command(flatten)]
+    pub download: DownloadOptions,
+    /// Also analyse each flag's SVG and report where it disagrees with the raster analysis
+    #[arg(long)]
+    pub vector: bool,
+    /// Results format: csv, json or ndjson.
+    /// CSV only lists the flags that were analysed, failures are just reported on stderr.
+    /// JSON and NDJSON have every detail per flag, failures and their errors included,
+    /// see schema/results.v1.schema.json
+    #[arg(long, default_value = "csv")]
+    pub format: OutputFormat,
+    /// Where to write the results, - for stdout [default: results.<format>]
+    #[arg(long, short, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Replace the output file if it already exists
+    #[arg(long)]
+    pub force: bool,
+    #[command(flatten)]
+    pub summary: SummaryOptions,
+    /// Also write a self-contained HTML page with every flag's thumbnail, scores and asymmetry heatmap
+    #[arg(long, value_name = "FILE")]
+    pub html: Option<PathBuf>,
+    /// Also write a PNG grid of the flags in each symmetry category into this directory,
+    /// full.png, horizontal.png, vertical.png and none.png
+    #[arg(long, value_name = "DIR")]
+    pub contact_sheets: Option<PathBuf>,
+    /// Flags per row on the contact sheets
+    #[arg(long, default_value_t = 8, requires = "contact_sheets")]
+    pub sheet_columns: usize,
+    /// Pixels around and between the flags on the contact sheets
+    #[arg(long, default_value_t = 16, requires = "contact_sheets")]
+    pub sheet_padding: u32,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// Flag code, alpha-3, numeric code, name or emoji, the path of an image file,
+    /// or - to read an image from stdin and print JSON
+    pub flag: String,
+    /// With -, the symmetry that makes the exit code 0 (1 without it, 2 on errors, which are
+    /// printed as JSON too): horizontal, vertical, rotational, full or any
+    #[arg(long, default_value = "any")]
+    pub axis: Axis,
+    #[command(flatten)]
+    pub download: DownloadOptions,
+    /// Also analyse the flag's SVG
+    #[arg(long)]
+    pub vector: bool,
+}
+
+#[derive(Args)]
+pub struct FilesArgs {
+    /// Image files, directories or glob patterns such as "mockups/*.png"
+    #[arg(required = true, value_name = "PATH")]
+    pub paths: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct FetchArgs {
+    #[command(flatten)]
+    pub selection: Selection,
+    #[command(flatten)]
+    pub download: DownloadOptions,
+    /// Also download each flag's SVG
+    #[arg(long)]
+    pub vector: bool,
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    /// Results file written by `run`, CSV, JSON or NDJSON
+    #[arg(default_value = DEFAULT_RESULTS_PATH)]
+    pub results: PathBuf,
+    /// Summary format: text or markdown
+    #[arg(long, default_value = "text")]
+    pub format: ReportFormat,
+    /// Replace the summary section of this markdown file (between the
+    /// <!-- summary:start --> and <!-- summary:end --> markers) instead of printing it
+    #[arg(long, value_name = "FILE")]
+    pub readme: Option<PathBuf>,
+    #[command(flatten)]
+    pub summary: SummaryOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Text,
+    Markdown, // the README's summary section
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(ReportFormat::Text),
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            _ => Err(format!(
+                "unknown report format '{}', expected text or markdown",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Earlier results file
+    pub old: PathBuf,
+    /// Later results file
+    pub new: PathBuf,
+}
+
+/// Which flags to analyse. Filters are applied before fetching so reruns on a subset are quick.
+#[derive(Args, Serialize)]
+pub struct Selection {
+    /// Flag datasets to include: countries, subdivisions, organizations [default: countries,organizations]
+    #[arg(long, value_delimiter = ',')]
+    pub datasets: Option<Vec<Dataset>>,
+    /// Order flags are listed in: code or name
+    #[arg(long, default_value = "code")]
+    pub sort: CountryOrder,
+    /// CSV or JSON file with extra flags, see user_dataset::load_dataset for the columns
+    #[arg(long, value_name = "FILE")]
+    pub dataset: Option<PathBuf>,
+    /// Only analyse the flags from --dataset
+    #[arg(long, requires = "dataset")]
+    pub dataset_only: bool,
+    /// Comma separated flags to analyse, e.g. "fr,Germany,🇯🇵"
+    #[arg(long, value_name = "FLAGS")]
+    pub only: Option<String>,
+    /// Comma separated flags to skip
+    #[arg(long, value_name = "FLAGS")]
+    pub exclude: Option<String>,
+    /// Comma separated regions, e.g. "europe,asia"
+    #[arg(long, value_name = "REGIONS")]
+    pub region: Option<String>,
+    /// Only sovereign states
+    #[arg(long, conflicts_with = "territories_only")]
+    pub sovereign_only: bool,
+    /// Only territories
+    #[arg(long)]
+    pub territories_only: bool,
+    /// Report country names in another language: en, fr, de, es or ja
+    #[arg(long, default_value = "en")]
+    pub lang: Lang,
+}
+
+/// How flags are listed under each category of the summary.
+#[derive(Args, Serialize)]
+pub struct SummaryOptions {
+    /// Order flags are listed in: code or name [default: the order of the results]
+    #[arg(long, value_name = "ORDER")]
+    pub summary_sort: Option<CountryOrder>,
+    /// Put each flag's emoji in front of its name
+    #[arg(long)]
+    pub summary_emoji: bool,
+}
+
+/// How flags are downloaded.
+#[derive(Args, Serialize)]
+pub struct DownloadOptions {
+    /// Requests per second sent to the flag CDN
+    #[arg(long, default_value_t = DEFAULT_REQUESTS_PER_SECOND, value_parser = parse_rate)]
+    pub rate: f64,
+    /// How many downloads can be in flight at once
+    #[arg(long, default_value_t = DEFAULT_CONCURRENCY)]
+    pub concurrency: usize,
+    #[arg(long, value_name = "SECONDS")]
+    pub connect_timeout: Option<u64>,
+    #[arg(long, value_name = "SECONDS")]
+    pub read_timeout: Option<u64>,
+    #[arg(long)]
+    pub user_agent: Option<String>,
+    /// Proxy for every request, overrides HTTPS_PROXY/HTTP_PROXY
+    #[arg(long)]
+    #[serde(skip)] // may carry credentials
+    pub proxy: Option<String>,
+    /// PEM file with an extra root certificate to trust
+    #[arg(long, value_name = "FILE")]
+    pub root_ca: Option<PathBuf>,
+    /// Fetch raster flags from a mirror instead of flagcdn, placeholders: {code} {CODE} {alpha3} {ALPHA3} {size} {ext}
+    #[arg(long, value_parser = UrlTemplate::parse)]
+    pub url_template: Option<UrlTemplate>,
+    /// Fetch SVG flags from a mirror instead of flagcdn
+    #[arg(long, value_parser = UrlTemplate::parse)]
+    pub svg_url_template: Option<UrlTemplate>,
+    /// CSV file tracking image hashes between runs
+    #[arg(long, value_name = "FILE")]
+    pub manifest: Option<PathBuf>,
+    /// Stop on any flag that changed since the manifest was written, cached flags included,
+    /// without writing results or updating the manifest
+    #[arg(long, requires = "manifest")]
+    pub strict_manifest: bool,
+    /// Read flags from this directory when present and keep new downloads there
+    #[arg(long, value_name = "DIR", default_value = DEFAULT_CACHE_DIR)]
+    pub cache_dir: PathBuf,
+    /// Always download, without reading or writing the cache
+    #[arg(long, conflicts_with = "cache_dir")]
+    pub no_cache: bool,
+}
+
+impl DownloadOptions {
+    /// The cache every command shares, so `fetch` followed by `run` doesn't download twice.
+    pub fn cache(&self) -> Option<&PathBuf> {
+        if self.no_cache {
+            None
+        } else {
+            Some(&self.cache_dir)
+        }
+    }
+}
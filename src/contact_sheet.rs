@@ -0,0 +1,272 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use crate::flags::FlagEntry;
+
+// DejaVu Sans Condensed, see assets/fonts/LICENSE-DejaVu.txt
+const FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSansCondensed.ttf");
+const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const TEXT: Rgba<u8> = Rgba([34, 34, 34, 255]);
+const FLAG_BORDER: Rgba<u8> = Rgba([221, 221, 221, 255]);
+const ELLIPSIS: char = '…';
+
+/// How a contact sheet is laid out. Flags are scaled to fit `cell_width` x `cell_height`
+/// keeping their aspect ratio, with their label underneath.
+#[derive(Debug, Clone, Copy)]
+pub struct SheetLayout {
+    pub columns: usize,
+    pub padding: u32, // around the sheet and between cells
+    pub cell_width: u32,
+    pub cell_height: u32,
+    pub label_size: f32, // pixel height of the labels, the title is twice this
+}
+
+impl Default for SheetLayout {
+    fn default() -> Self {
+        SheetLayout {
+            columns: 8,
+            padding: 16,
+            cell_width: 160,
+            cell_height: 100,
+            label_size: 16.0,
+        }
+    }
+}
+
+/// Composes a grid of flags with a title on top, e.g. every flag with full symmetry.
+///
+/// ```
+/// use flag_symmetry::contact_sheet::{contact_sheet, SheetLayout};
+/// use image::{DynamicImage, RgbaImage};
+///
+/// let flag = DynamicImage::ImageRgba8(RgbaImage::new(30, 20));
+/// let flags = vec![("Japan", &flag), ("Thailand", &flag), ("Austria", &flag)];
+/// let layout = SheetLayout { columns: 2, ..SheetLayout::default() };
+///
+/// let sheet = contact_sheet("Full symmetry", &flags, &layout);
+/// assert_eq!(sheet.width(), 16 + 2 * (160 + 16));
+/// ```
+pub fn contact_sheet(
+    title: &str,
+    flags: &[(&str, &DynamicImage)],
+    layout: &SheetLayout,
+) -> RgbaImage {
+    let font = FontRef::try_from_slice(FONT).expect("the embedded font is valid");
+    let columns = layout.columns.max(1);
+    let rows = flags.len().div_ceil(columns);
+    let title_height = (layout.label_size * 2.0).ceil() as u32;
+    let label_height = (layout.label_size * 1.5).ceil() as u32;
+    let cell_stride_x = layout.cell_width + layout.padding;
+    let cell_stride_y = layout.cell_height + label_height + layout.padding;
+
+    let width = layout.padding + columns as u32 * cell_stride_x;
+    let height = 2 * layout.padding + title_height + rows as u32 * cell_stride_y;
+    let mut sheet = RgbaImage::from_pixel(width, height, BACKGROUND);
+
+    let title_scale = PxScale::from(layout.label_size * 2.0);
+    let title = fit_text(&font, title_scale, title, width - 2 * layout.padding);
+    draw_text(
+        &mut sheet,
+        &font,
+        title_scale,
+        &title,
+        width / 2,
+        layout.padding,
+    );
+
+    let label_scale = PxScale::from(layout.label_size);
+    let top = 2 * layout.padding + title_height;
+    for (i, (label, flag)) in flags.iter().enumerate() {
+        let x = layout.padding + (i % columns) as u32 * cell_stride_x;
+        let y = top + (i / columns) as u32 * cell_stride_y;
+
+        let scaled = flag.resize(layout.cell_width, layout.cell_height, FilterType::Triangle);
+        let (flag_width, flag_height) = scaled.dimensions();
+        // flags sit at the bottom of their cell so the labels in a row line up
+        let flag_x = x + (layout.cell_width - flag_width) / 2;
+        let flag_y = y + layout.cell_height - flag_height;
+        draw_border(&mut sheet, flag_x, flag_y, flag_width, flag_height);
+        imageops::overlay(&mut sheet, &scaled, flag_x as i64, flag_y as i64);
+
+        let label = fit_text(&font, label_scale, label, layout.cell_width);
+        let label_y = y + layout.cell_height + label_height - layout.label_size.ceil() as u32;
+        draw_text(
+            &mut sheet,
+            &font,
+            label_scale,
+            &label,
+            x + layout.cell_width / 2,
+            label_y,
+        );
+    }
+
+    sheet
+}
+
+/// The label a flag gets on a contact sheet. The embedded font has no CJK glyphs, so names it
+/// can't draw, e.g. with `--lang ja`, fall back to the English name and then to the code.
+pub fn sheet_label(entry: &FlagEntry) -> String {
+    let font = FontRef::try_from_slice(FONT).expect("the embedded font is valid");
+    let drawable = |text: &str| {
+        text.chars()
+            .all(|c| c.is_whitespace() || font.glyph_id(c).0 != 0)
+    };
+    [
+        Some(entry.name.as_str()),
+        entry.country.map(|country| country.name),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|name| drawable(name))
+    .unwrap_or(&entry.id)
+    .to_string()
+}
+
+fn text_width(font: &FontRef, scale: PxScale, text: &str) -> f32 {
+    let font = font.as_scaled(scale);
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let glyph = font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += font.kern(previous, glyph);
+        }
+        width += font.h_advance(glyph);
+        previous = Some(glyph);
+    }
+    width
+}
+
+// shortens text that doesn't fit with an ellipsis, long names like "South Georgia and the South Sandwich Islands"
+fn fit_text(font: &FontRef, scale: PxScale, text: &str, max_width: u32) -> String {
+    if text_width(font, scale, text) <= max_width as f32 {
+        return text.to_string();
+    }
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let kept: String = chars.iter().collect();
+        let shortened = format!("{}{}", kept.trim_end(), ELLIPSIS);
+        if text_width(font, scale, &shortened) <= max_width as f32 {
+            return shortened;
+        }
+    }
+    String::new()
+}
+
+// draws a single line of text centered on `center_x`, with the top of the line at `top`
+fn draw_text(
+    canvas: &mut RgbaImage,
+    font: &FontRef,
+    scale: PxScale,
+    text: &str,
+    center_x: u32,
+    top: u32,
+) {
+    let scaled = font.as_scaled(scale);
+    let mut caret = center_x as f32 - text_width(font, scale, text) / 2.0;
+    let baseline = top as f32 + scaled.ascent();
+    let mut previous = None;
+
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(scale, point(caret, baseline));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue; // spaces have no outline
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|x, y, coverage| {
+            let x = bounds.min.x as i32 + x as i32;
+            let y = bounds.min.y as i32 + y as i32;
+            if x < 0 || y < 0 || x >= canvas.width() as i32 || y >= canvas.height() as i32 {
+                return;
+            }
+            let pixel = canvas.get_pixel_mut(x as u32, y as u32);
+            *pixel = blend(*pixel, TEXT, coverage);
+        });
+    }
+}
+
+// a one pixel frame so white flags (and white stripes on the edge) don't vanish into the background
+fn draw_border(canvas: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32) {
+    let left = x.saturating_sub(1);
+    let top = y.saturating_sub(1);
+    let right = (x + width).min(canvas.width() - 1);
+    let bottom = (y + height).min(canvas.height() - 1);
+    for px in left..=right {
+        canvas.put_pixel(px, top, FLAG_BORDER);
+        canvas.put_pixel(px, bottom, FLAG_BORDER);
+    }
+    for py in top..=bottom {
+        canvas.put_pixel(left, py, FLAG_BORDER);
+        canvas.put_pixel(right, py, FLAG_BORDER);
+    }
+}
+
+fn blend(background: Rgba<u8>, foreground: Rgba<u8>, coverage: f32) -> Rgba<u8> {
+    let coverage = coverage.clamp(0.0, 1.0);
+    let mut blended = background;
+    for i in 0..3 {
+        blended.0[i] = (background.0[i] as f32 * (1.0 - coverage)
+            + foreground.0[i] as f32 * coverage)
+            .round() as u8;
+    }
+    blended
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::countries::lookup;
+    use crate::flags::FlagSource;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+    #[test]
+    fn lays_flags_out_in_rows() {
+        let flag = DynamicImage::ImageRgba8(RgbaImage::from_pixel(30, 20, RED));
+        let flags = vec![("Flag", &flag); 5];
+        let layout = SheetLayout {
+            columns: 2,
+            ..SheetLayout::default()
+        };
+
+        let sheet = contact_sheet("Title", &flags, &layout);
+
+        // 16px padding, a 32px title and three rows of 100px cells with 24px labels
+        assert_eq!(sheet.dimensions(), (16 + 2 * 176, 2 * 16 + 32 + 3 * 140));
+        // the fifth flag starts the third row, scaled to 150x100 and centered in its cell
+        let (left, top) = (16 + 5, 2 * 16 + 32 + 2 * 140);
+        assert_eq!(*sheet.get_pixel(left - 1, top - 1), FLAG_BORDER);
+        assert_eq!(*sheet.get_pixel(left + 150, top + 100), FLAG_BORDER);
+        assert_eq!(*sheet.get_pixel(left, top), RED);
+        assert_eq!(*sheet.get_pixel(left + 149, top + 99), RED);
+        // and the cell next to it stays empty
+        assert_eq!(*sheet.get_pixel(16 + 176 + 80, top + 50), BACKGROUND);
+    }
+
+    #[test]
+    fn labels_fall_back_when_the_font_has_no_glyphs() {
+        let mut japan = FlagEntry::from_country(lookup("jp").unwrap());
+        assert_eq!(sheet_label(&japan), "Japan");
+
+        japan.name = "日本".to_string();
+        assert_eq!(sheet_label(&japan), "Japan");
+
+        let custom = FlagEntry {
+            id: "tokyo".to_string(),
+            name: "東京都".to_string(),
+            emoji: String::new(),
+            source: FlagSource::Url("https://example.com/tokyo.png".to_string()),
+            country: None,
+        };
+        assert_eq!(sheet_label(&custom), "tokyo");
+    }
+}
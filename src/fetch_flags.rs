@@ -1,36 +1,850 @@
 use bytes::Bytes;
 use image::io::Reader as ImageReader;
 use image::{DynamicImage, ImageError, ImageFormat};
-use std::io::Cursor;
+use reqwest::header::{CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Certificate, Client, Proxy, Response, StatusCode};
+use std::fmt;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
 
-const FLAG_SIZE: &'static str = "h80";
-const FLAG_API_URL: &'static str = "https://flagcdn.com";
+use crate::atomic_file::AtomicFile;
+use crate::manifest::{sha256_hex, FlagChange, Manifest};
+use crate::url_template::UrlTemplate;
 
-fn load_image_from_bytes(bytes: &Bytes) -> Result<DynamicImage, ImageError> {
+const FLAG_SIZE: &str = "h80";
+const FLAG_EXT: &str = "png";
+pub const DEFAULT_URL_TEMPLATE: &str = "https://flagcdn.com/{size}/{code}.{ext}";
+pub const DEFAULT_SVG_URL_TEMPLATE: &str = "https://flagcdn.com/{code}.svg";
+
+pub const DEFAULT_REQUESTS_PER_SECOND: f64 = 10.0;
+
+/// Parses a requests per second limit, which has to be a positive finite number.
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => Err(format!(
+            "invalid rate '{}', expected a number of requests per second above 0",
+            s
+        )),
+    }
+}
+const MAX_RETRIES: u32 = 5;
+const BASE_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// formats we decode, anything else is reported as unsupported
+const SUPPORTED_FORMATS: [ImageFormat; 5] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::Gif,
+    ImageFormat::WebP,
+    ImageFormat::Bmp,
+];
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_USER_AGENT: &str = concat!("flag_symmetry/", env!("CARGO_PKG_VERSION"));
+
+/// Settings for the HTTP client shared by every download.
+/// Proxies from the usual `HTTPS_PROXY`/`HTTP_PROXY` env vars are picked up unless `proxy` overrides them.
+pub struct ClientConfig {
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+    pub user_agent: String,
+    pub proxy: Option<String>,
+    pub root_ca: Option<PathBuf>, // PEM file with an extra root certificate to trust
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            proxy: None,
+            root_ca: None,
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn build(&self) -> Result<Client, Box<dyn std::error::Error>> {
+        let mut builder = Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .read_timeout(self.read_timeout)
+            .user_agent(&self.user_agent);
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        if let Some(path) = &self.root_ca {
+            let pem = std::fs::read(path)?;
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+#[derive(Debug)]
+pub enum FetchError {
+    Request(reqwest::Error),
+    UnsupportedFormat(String), // the detected format, or the Content-Type if sniffing failed
+    Decode(ImageError),
+    ManifestMismatch(FlagChange), // only raised when the fetcher is in strict manifest mode
+    Io(std::io::Error),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Request(err) => write!(f, "request failed: {}", err),
+            FetchError::UnsupportedFormat(format) => {
+                write!(f, "unsupported image format: {}", format)
+            }
+            FetchError::Decode(err) => write!(f, "failed to decode image: {}", err),
+            FetchError::ManifestMismatch(change) => write!(f, "unexpected flag change: {}", change),
+            FetchError::Io(err) => write!(f, "failed to read image: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FetchError::Request(err) => Some(err),
+            FetchError::UnsupportedFormat(_) => None,
+            FetchError::Decode(err) => Some(err),
+            FetchError::ManifestMismatch(_) => None,
+            FetchError::Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(err: reqwest::Error) -> Self {
+        FetchError::Request(err)
+    }
+}
+
+impl From<std::io::Error> for FetchError {
+    fn from(err: std::io::Error) -> Self {
+        FetchError::Io(err)
+    }
+}
+
+impl From<ImageError> for FetchError {
+    fn from(err: ImageError) -> Self {
+        FetchError::Decode(err)
+    }
+}
+
+/// Token bucket that allows short bursts but holds the average request rate to `rate` per second.
+struct RateLimiter {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    fn new(rate: f64) -> Self {
+        // a zero, negative or NaN rate would make acquire's wait time invalid
+        assert!(
+            rate.is_finite() && rate > 0.0,
+            "request rate must be positive, got {}",
+            rate
+        );
+        let capacity = rate.max(1.0);
+        RateLimiter {
+            rate,
+            capacity,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+                state.tokens = (state.tokens + elapsed * self.rate).min(self.capacity);
+                state.last_refill = now;
+
+                if state.tokens >= 1.0 {
+                    state.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - state.tokens) / self.rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Downloads flags with a shared client, a request rate limit and retries on transient failures.
+///
+/// ```no_run
+/// use flag_symmetry::{check_symmetry, ClientConfig, FlagFetcher};
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let fetcher = FlagFetcher::new(ClientConfig::default().build()?, 10.0);
+/// let flag = fetcher.get_flag("jp").await?;
+/// println!("{:?}", check_symmetry(&flag));
+/// # Ok(())
+/// # }
+/// ```
+pub struct FlagFetcher {
+    client: Client,
+    url_template: UrlTemplate,
+    svg_url_template: UrlTemplate,
+    limiter: RateLimiter,
+    retries: AtomicU32,
+    manifest: Option<Mutex<Manifest>>,
+    strict_manifest: bool,
+    cache_dir: Option<PathBuf>,
+}
+
+impl FlagFetcher {
+    /// Panics if `requests_per_second` isn't a positive finite number, see `parse_rate`.
+    pub fn new(client: Client, requests_per_second: f64) -> Self {
+        FlagFetcher {
+            client,
+            url_template: UrlTemplate::parse(DEFAULT_URL_TEMPLATE).unwrap(),
+            svg_url_template: UrlTemplate::parse(DEFAULT_SVG_URL_TEMPLATE).unwrap(),
+            limiter: RateLimiter::new(requests_per_second),
+            retries: AtomicU32::new(0),
+            manifest: None,
+            strict_manifest: false,
+            cache_dir: None,
+        }
+    }
+
+    /// Fetch raster flags from `url_template` instead of flagcdn, e.g. an internal mirror.
+    pub fn with_url_template(mut self, url_template: UrlTemplate) -> Self {
+        self.url_template = url_template;
+        self
+    }
+
+    pub fn with_svg_url_template(mut self, svg_url_template: UrlTemplate) -> Self {
+        self.svg_url_template = svg_url_template;
+        self
+    }
+
+    /// Hash every download into `manifest`. With `strict`, a flag whose hash changed is returned as
+    /// `FetchError::ManifestMismatch` instead of being analysed.
+    pub fn with_manifest(mut self, manifest: Manifest, strict: bool) -> Self {
+        self.manifest = Some(Mutex::new(manifest));
+        self.strict_manifest = strict;
+        self
+    }
+
+    /// Keep downloads under `cache_dir` and read them back from there instead of the network.
+    /// Entries are keyed on a hash of the URL, so a different mirror or dataset URL is fetched
+    /// afresh. Cached flags are checked against the manifest like downloads.
+    pub fn with_cache(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = Some(cache_dir);
+        self
+    }
+
+    pub fn manifest(&self) -> Option<MutexGuard<'_, Manifest>> {
+        self.manifest
+            .as_ref()
+            .map(|manifest| manifest.lock().unwrap())
+    }
+
+    /// Total number of retried requests since the fetcher was created.
+    pub fn retry_count(&self) -> u32 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    /// Where `get_flag` downloads a registry flag from.
+    pub fn flag_url(&self, country_code: &str) -> String {
+        self.url_template.render(country_code, FLAG_SIZE, FLAG_EXT)
+    }
+
+    pub async fn get_flag(&self, country_code: &str) -> Result<DynamicImage, FetchError> {
+        let url = self.flag_url(country_code);
+        self.get_flag_from_url(country_code, &url).await
+    }
+
+    /// Downloads a flag from an explicit URL, `id` is what the manifest records it under.
+    pub async fn get_flag_from_url(&self, id: &str, url: &str) -> Result<DynamicImage, FetchError> {
+        if let Some(bytes) = self.read_cache(url)? {
+            // an entry that no longer decodes is downloaded again and replaced
+            if let Ok(img) = load_image_from_bytes(&bytes, None) {
+                self.check_manifest(id, FLAG_SIZE, &bytes)?;
+                return Ok(img);
+            }
+        }
+
+        let response = self.fetch(url).await?;
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        let bytes = response.bytes().await?;
+        // only record and cache what decodes, so an error page doesn't stick around
+        let img = load_image_from_bytes(&bytes, content_type.as_deref())?;
+        self.check_manifest(id, FLAG_SIZE, &bytes)?;
+        self.write_cache(url, &bytes)?;
+        Ok(img)
+    }
+
+    pub async fn get_flag_svg(&self, country_code: &str) -> Result<Bytes, FetchError> {
+        let url = self.svg_url_template.render(country_code, FLAG_SIZE, "svg");
+        if let Some(bytes) = self.read_cache(&url)? {
+            self.check_manifest(country_code, "svg", &bytes)?;
+            return Ok(bytes);
+        }
+
+        let bytes = self.fetch(&url).await?.bytes().await?;
+        // like rasters, only record and cache an SVG that parses, the caller reports the parse error
+        if usvg::Tree::from_data(&bytes, &usvg::Options::default()).is_ok() {
+            self.check_manifest(country_code, "svg", &bytes)?;
+            self.write_cache(&url, &bytes)?;
+        }
+        Ok(bytes)
+    }
+
+    fn cache_path(&self, url: &str) -> Option<PathBuf> {
+        // the URL rather than the id, which a user dataset can reuse and a mirror doesn't change
+        Some(self.cache_dir.as_ref()?.join(sha256_hex(url.as_bytes())))
+    }
+
+    fn read_cache(&self, url: &str) -> Result<Option<Bytes>, FetchError> {
+        match self.cache_path(url) {
+            Some(path) if path.exists() => Ok(Some(Bytes::from(std::fs::read(path)?))),
+            _ => Ok(None),
+        }
+    }
+
+    fn write_cache(&self, url: &str, bytes: &[u8]) -> Result<(), FetchError> {
+        let Some(path) = self.cache_path(url) else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // an interrupted write must not leave a truncated entry that's read back on every run
+        let mut file = AtomicFile::create(&path, true)?;
+        file.write_all(bytes)?;
+        file.commit()?;
+        Ok(())
+    }
+
+    fn check_manifest(
+        &self,
+        country_code: &str,
+        size: &str,
+        bytes: &[u8],
+    ) -> Result<(), FetchError> {
+        let Some(mut manifest) = self.manifest() else {
+            return Ok(());
+        };
+        let sha256 = sha256_hex(bytes);
+
+        if self.strict_manifest {
+            if let Some(entry) = manifest.lookup(country_code, size) {
+                if entry.sha256 != sha256 {
+                    return Err(FetchError::ManifestMismatch(FlagChange {
+                        code: country_code.to_string(),
+                        size: size.to_string(),
+                        old_sha256: entry.sha256.clone(),
+                        new_sha256: sha256,
+                        old_fetched_at: entry.fetched_at.clone(),
+                    }));
+                }
+            }
+        }
+
+        manifest.record(country_code, size, sha256);
+        Ok(())
+    }
+
+    async fn fetch(&self, url: &str) -> Result<Response, reqwest::Error> {
+        let mut attempt = 0;
+        loop {
+            self.limiter.acquire().await;
+            let result = self.client.get(url).send().await;
+
+            let retry_delay = match &result {
+                Ok(response) if is_retryable_status(response.status()) => {
+                    Some(retry_after(response).unwrap_or_else(|| backoff(attempt)))
+                }
+                Err(err) if err.is_timeout() || err.is_connect() || err.is_request() => {
+                    Some(backoff(attempt))
+                }
+                _ => None,
+            };
+
+            match retry_delay {
+                Some(delay) if attempt < MAX_RETRIES => {
+                    attempt += 1;
+                    self.retries.fetch_add(1, Ordering::Relaxed);
+                    tokio::time::sleep(delay).await;
+                }
+                _ => return result?.error_for_status(),
+            }
+        }
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn backoff(attempt: u32) -> Duration {
+    // "full jitter": pick a random delay up to the exponential cap so retrying clients spread out
+    let cap = BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF);
+    cap.mul_f64(fastrand::f64())
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    // Retry-After is either a number of seconds or an HTTP date
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    let delay = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .unwrap_or_default(),
+    };
+    Some(delay.min(MAX_BACKOFF))
+}
+
+fn detect_format(bytes: &[u8], content_type: Option<&str>) -> Result<ImageFormat, FetchError> {
+    // Trust the magic bytes first since servers often send a generic or wrong Content-Type
+    let mime_type = content_type.map(|value| value.split(';').next().unwrap_or("").trim());
+    let format = image::guess_format(bytes)
+        .ok()
+        .or_else(|| mime_type.and_then(ImageFormat::from_mime_type));
+
+    match format {
+        Some(format) if SUPPORTED_FORMATS.contains(&format) => Ok(format),
+        Some(format) => Err(FetchError::UnsupportedFormat(format!("{:?}", format))),
+        None => Err(FetchError::UnsupportedFormat(
+            mime_type.unwrap_or("unknown").to_string(),
+        )),
+    }
+}
+
+/// Loads a flag from disk with the same format detection as downloads.
+pub fn load_flag_file(path: &Path) -> Result<DynamicImage, FetchError> {
+    let bytes = Bytes::from(std::fs::read(path)?);
+    load_image_from_bytes(&bytes, None)
+}
+
+/// Decodes an image of unknown origin, e.g. piped into stdin, sniffing the format from its magic bytes.
+pub fn load_flag_bytes(bytes: &[u8]) -> Result<DynamicImage, FetchError> {
+    load_image_from_bytes(&Bytes::copy_from_slice(bytes), None)
+}
+
+/// Whether `path` has the extension of an image format `load_flag_file` can decode.
+pub fn is_supported_image(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok_and(|format| SUPPORTED_FORMATS.contains(&format))
+}
+
+fn load_image_from_bytes(
+    bytes: &Bytes,
+    content_type: Option<&str>,
+) -> Result<DynamicImage, FetchError> {
+    let format = detect_format(bytes, content_type)?;
     let cursor = Cursor::new(bytes);
     let mut reader = ImageReader::new(cursor);
-    reader.set_format(ImageFormat::Png);
+    reader.set_format(format);
     let img = reader.decode()?;
     Ok(img)
 }
 
-fn create_flag_url(country_code: &str) -> String {
-    format!("{}/{}/{}.png", FLAG_API_URL, FLAG_SIZE, country_code)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{fixture_flag, Reply, TestServer};
+    use image::GenericImageView;
+    use tempfile::tempdir;
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+
+    fn test_fetcher(server: &TestServer) -> FlagFetcher {
+        let config = ClientConfig {
+            read_timeout: Duration::from_millis(200),
+            ..ClientConfig::default()
+        };
+        FlagFetcher::new(config.build().unwrap(), 1000.0)
+            .with_url_template(server.url_template())
+            .with_svg_url_template(server.svg_url_template())
+    }
+
+    fn png_bytes(img: &image::RgbaImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
 
-pub async fn get_flag(country_code: &str) -> Result<DynamicImage, reqwest::Error> {
-    let url = create_flag_url(country_code);
-    let response = reqwest::get(&url).await?;
+    fn cache_entry(dir: &Path, url: &str) -> PathBuf {
+        dir.join(sha256_hex(url.as_bytes()))
+    }
+
+    #[tokio::test]
+    async fn fetches_and_decodes_a_flag() {
+        let server = TestServer::start().await;
+        server.route("/h80/fr.png", vec![Reply::png(&fixture_flag(RED, BLUE))]);
+        let fetcher = test_fetcher(&server);
+
+        let img = fetcher.get_flag("fr").await.unwrap();
+
+        assert_eq!(img.dimensions(), (12, 8));
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(fetcher.retry_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn fetches_an_svg() {
+        let server = TestServer::start().await;
+        let svg = b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>".to_vec();
+        server.route(
+            "/fr.svg",
+            vec![Reply::new(200, "image/svg+xml", svg.clone())],
+        );
+        let fetcher = test_fetcher(&server);
+
+        let bytes = fetcher.get_flag_svg("fr").await.unwrap();
+
+        assert_eq!(bytes.as_ref(), svg.as_slice());
+    }
 
-    if !response.status().is_success() {
-        panic!(
-            "Failed to fetch the flag for country code {}. Status code: {}",
-            country_code,
-            response.status()
+    #[tokio::test]
+    async fn retries_rate_limited_requests() {
+        let server = TestServer::start().await;
+        let too_many = Reply::status(429).header("Retry-After", "0");
+        server.route(
+            "/h80/fr.png",
+            vec![
+                too_many.clone(),
+                too_many,
+                Reply::png(&fixture_flag(RED, BLUE)),
+            ],
         );
+        let fetcher = test_fetcher(&server);
+
+        assert!(fetcher.get_flag("fr").await.is_ok());
+        assert_eq!(fetcher.retry_count(), 2);
+        assert_eq!(server.hits("/h80/fr.png"), 3);
     }
 
-    let bytes = response.bytes().await?;
-    let img: DynamicImage = load_image_from_bytes(&bytes).expect("Failed to load image from bytes");
-    Ok(img)
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let server = TestServer::start().await;
+        server.route(
+            "/h80/fr.png",
+            vec![Reply::status(503), Reply::png(&fixture_flag(RED, BLUE))],
+        );
+        let fetcher = test_fetcher(&server);
+
+        assert!(fetcher.get_flag("fr").await.is_ok());
+        assert_eq!(fetcher.retry_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_slow_responses() {
+        let server = TestServer::start().await;
+        let slow = Reply::png(&fixture_flag(RED, BLUE)).delayed(Duration::from_secs(1));
+        server.route(
+            "/h80/fr.png",
+            vec![slow, Reply::png(&fixture_flag(RED, BLUE))],
+        );
+        let fetcher = test_fetcher(&server);
+
+        assert!(fetcher.get_flag("fr").await.is_ok());
+        assert_eq!(fetcher.retry_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let server = TestServer::start().await;
+        server.route(
+            "/h80/fr.png",
+            vec![Reply::status(429).header("Retry-After", "0")],
+        );
+        let fetcher = test_fetcher(&server);
+
+        let err = fetcher.get_flag("fr").await.unwrap_err();
+
+        match err {
+            FetchError::Request(err) => {
+                assert_eq!(err.status(), Some(StatusCode::TOO_MANY_REQUESTS))
+            }
+            other => panic!("expected a request error, got {:?}", other),
+        }
+        assert_eq!(fetcher.retry_count(), MAX_RETRIES);
+    }
+
+    #[tokio::test]
+    async fn reports_missing_flags_without_retrying() {
+        let server = TestServer::start().await;
+        let fetcher = test_fetcher(&server);
+
+        let err = fetcher.get_flag("zz").await.unwrap_err();
+
+        match err {
+            FetchError::Request(err) => assert_eq!(err.status(), Some(StatusCode::NOT_FOUND)),
+            other => panic!("expected a request error, got {:?}", other),
+        }
+        assert_eq!(server.hits("/h80/zz.png"), 1);
+    }
+
+    #[tokio::test]
+    async fn reports_corrupt_images() {
+        let server = TestServer::start().await;
+        let mut corrupt = Vec::from(*b"\x89PNG\r\n\x1a\n");
+        corrupt.extend_from_slice(b"definitely not a png");
+        server.route("/h80/fr.png", vec![Reply::new(200, "image/png", corrupt)]);
+        let fetcher = test_fetcher(&server);
+
+        let err = fetcher.get_flag("fr").await.unwrap_err();
+
+        assert!(matches!(err, FetchError::Decode(_)), "got {:?}", err);
+    }
+
+    #[tokio::test]
+    async fn reports_unsupported_formats() {
+        let server = TestServer::start().await;
+        let html = b"<html>maintenance</html>".to_vec();
+        server.route("/h80/fr.png", vec![Reply::new(200, "text/html", html)]);
+        let fetcher = test_fetcher(&server);
+
+        let err = fetcher.get_flag("fr").await.unwrap_err();
+
+        match err {
+            FetchError::UnsupportedFormat(format) => assert_eq!(format, "text/html"),
+            other => panic!("expected an unsupported format error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn manifest_reports_changed_flags() {
+        let server = TestServer::start().await;
+        server.route("/h80/fr.png", vec![Reply::png(&fixture_flag(RED, BLUE))]);
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("manifest.csv");
+
+        let fetcher = test_fetcher(&server).with_manifest(Manifest::load(&path).unwrap(), false);
+        fetcher.get_flag("fr").await.unwrap();
+        fetcher.manifest().unwrap().save().unwrap();
+
+        server.route("/h80/fr.png", vec![Reply::png(&fixture_flag(BLUE, RED))]);
+        let fetcher = test_fetcher(&server).with_manifest(Manifest::load(&path).unwrap(), false);
+        fetcher.get_flag("fr").await.unwrap();
+
+        let manifest = fetcher.manifest().unwrap();
+        assert_eq!(manifest.changes().len(), 1);
+        assert_eq!(manifest.changes()[0].code, "fr");
+    }
+
+    #[tokio::test]
+    async fn strict_manifest_refuses_changed_flags() {
+        let server = TestServer::start().await;
+        server.route("/h80/fr.png", vec![Reply::png(&fixture_flag(RED, BLUE))]);
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("manifest.csv");
+
+        let fetcher = test_fetcher(&server).with_manifest(Manifest::load(&path).unwrap(), true);
+        fetcher.get_flag("fr").await.unwrap();
+        fetcher.manifest().unwrap().save().unwrap();
+
+        server.route("/h80/fr.png", vec![Reply::png(&fixture_flag(BLUE, RED))]);
+        let fetcher = test_fetcher(&server).with_manifest(Manifest::load(&path).unwrap(), true);
+        let err = fetcher.get_flag("fr").await.unwrap_err();
+
+        assert!(
+            matches!(err, FetchError::ManifestMismatch(_)),
+            "got {:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn undecodable_downloads_are_not_recorded() {
+        let server = TestServer::start().await;
+        server.route(
+            "/h80/fr.png",
+            vec![Reply::new(200, "text/html", b"<html>moved</html>".to_vec())],
+        );
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("manifest.csv");
+
+        let fetcher = test_fetcher(&server).with_manifest(Manifest::load(&path).unwrap(), false);
+        fetcher.get_flag("fr").await.unwrap_err();
+
+        assert!(fetcher.manifest().unwrap().lookup("fr", "h80").is_none());
+    }
+
+    #[tokio::test]
+    async fn unchanged_flags_keep_their_fetched_at() {
+        let server = TestServer::start().await;
+        let png = png_bytes(&fixture_flag(RED, BLUE));
+        server.route("/h80/fr.png", vec![Reply::png(&fixture_flag(RED, BLUE))]);
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("manifest.csv");
+        let fetched_at = "Mon, 01 Jan 2024 00:00:00 GMT";
+        std::fs::write(
+            &path,
+            format!(
+                "code,size,sha256,fetched_at\nfr,h80,{},\"{}\"\n",
+                sha256_hex(&png),
+                fetched_at
+            ),
+        )
+        .unwrap();
+
+        let fetcher = test_fetcher(&server).with_manifest(Manifest::load(&path).unwrap(), false);
+        fetcher.get_flag("fr").await.unwrap();
+
+        let manifest = fetcher.manifest().unwrap();
+        assert_eq!(manifest.lookup("fr", "h80").unwrap().fetched_at, fetched_at);
+        assert!(manifest.changes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cached_flags_skip_the_network() {
+        let server = TestServer::start().await;
+        server.route("/h80/fr.png", vec![Reply::png(&fixture_flag(RED, BLUE))]);
+        let temp = tempdir().unwrap();
+        let dir = temp.path().join("cache");
+
+        test_fetcher(&server)
+            .with_cache(dir.clone())
+            .get_flag("fr")
+            .await
+            .unwrap();
+        let img = test_fetcher(&server)
+            .with_cache(dir.clone())
+            .get_flag("fr")
+            .await
+            .unwrap();
+
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(server.hits("/h80/fr.png"), 1);
+        let url = server.url_template().render("fr", FLAG_SIZE, FLAG_EXT);
+        assert!(cache_entry(&dir, &url).exists());
+    }
+
+    #[tokio::test]
+    async fn cache_is_keyed_on_the_url() {
+        // a dataset flag reusing a registry id must not be served the registry flag
+        let server = TestServer::start().await;
+        server.route("/h80/fr.png", vec![Reply::png(&fixture_flag(RED, BLUE))]);
+        server.route("/club/fr.png", vec![Reply::png(&fixture_flag(BLUE, RED))]);
+        let temp = tempdir().unwrap();
+        let dir = temp.path().join("cache");
+        let fetcher = test_fetcher(&server).with_cache(dir.clone());
+
+        fetcher.get_flag("fr").await.unwrap();
+        let club_url = fetcher.flag_url("fr").replace("/h80/", "/club/");
+        let club = fetcher.get_flag_from_url("fr", &club_url).await.unwrap();
+
+        assert_eq!(club.get_pixel(0, 0).0, [0, 0, 255, 255]);
+        assert_eq!(server.hits("/club/fr.png"), 1);
+    }
+
+    #[test]
+    fn rejects_rates_that_are_not_positive() {
+        assert_eq!(parse_rate("2.5"), Ok(2.5));
+        for rate in ["0", "-1", "NaN", "inf", "fast"] {
+            assert!(parse_rate(rate).is_err(), "{}", rate);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "request rate must be positive")]
+    fn limiter_refuses_a_zero_rate() {
+        RateLimiter::new(0.0);
+    }
+
+    #[tokio::test]
+    async fn cached_svgs_skip_the_network() {
+        let server = TestServer::start().await;
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="6" height="4"/>"#;
+        server.route(
+            "/fr.svg",
+            vec![Reply::new(200, "image/svg+xml", svg.into())],
+        );
+        let temp = tempdir().unwrap();
+        let dir = temp.path().join("cache");
+
+        for _ in 0..2 {
+            let fetcher = test_fetcher(&server).with_cache(dir.clone());
+            assert_eq!(fetcher.get_flag_svg("fr").await.unwrap(), svg.as_bytes());
+        }
+
+        assert_eq!(server.hits("/fr.svg"), 1);
+    }
+
+    #[tokio::test]
+    async fn corrupt_cache_entries_are_downloaded_again() {
+        let server = TestServer::start().await;
+        server.route("/h80/fr.png", vec![Reply::png(&fixture_flag(RED, BLUE))]);
+        let temp = tempdir().unwrap();
+        let dir = temp.path().join("cache");
+        let fetcher = test_fetcher(&server).with_cache(dir.clone());
+        let entry = cache_entry(&dir, &fetcher.flag_url("fr"));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&entry, b"\x89PNG truncated").unwrap();
+
+        let img = fetcher.get_flag("fr").await.unwrap();
+
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(server.hits("/h80/fr.png"), 1);
+        assert_eq!(
+            std::fs::read(&entry).unwrap(),
+            png_bytes(&fixture_flag(RED, BLUE))
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_flags_are_checked_against_the_manifest() {
+        let server = TestServer::start().await;
+        server.route("/h80/fr.png", vec![Reply::png(&fixture_flag(RED, BLUE))]);
+        let temp = tempdir().unwrap();
+        let dir = temp.path().join("cache");
+        let path = temp.path().join("manifest.csv");
+
+        let fetcher = test_fetcher(&server)
+            .with_cache(dir.clone())
+            .with_manifest(Manifest::load(&path).unwrap(), true);
+        fetcher.get_flag("fr").await.unwrap();
+        fetcher.manifest().unwrap().save().unwrap();
+        // the cache now disagrees with the manifest, e.g. it was filled from another mirror
+        let entry = cache_entry(&dir, &fetcher.flag_url("fr"));
+        std::fs::write(&entry, png_bytes(&fixture_flag(BLUE, RED))).unwrap();
+
+        let fetcher = test_fetcher(&server)
+            .with_cache(dir.clone())
+            .with_manifest(Manifest::load(&path).unwrap(), true);
+        let err = fetcher.get_flag("fr").await.unwrap_err();
+
+        assert!(
+            matches!(err, FetchError::ManifestMismatch(_)),
+            "got {:?}",
+            err
+        );
+        assert_eq!(server.hits("/h80/fr.png"), 1);
+    }
 }
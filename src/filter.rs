@@ -0,0 +1,171 @@
+use flag_symmetry::consts::countries::{Region, Status};
+use flag_symmetry::flags::FlagEntry;
+use flag_symmetry::resolve::resolve;
+
+/// Narrows down which flags get analysed. Empty lists mean "no restriction".
+/// Region and status only match flags from the built-in registry.
+#[derive(Debug, Default)]
+pub struct CountryFilter {
+    pub only: Vec<String>,
+    pub exclude: Vec<String>,
+    pub regions: Vec<Region>,
+    pub status: Option<Status>,
+}
+
+impl CountryFilter {
+    /// Splits a comma separated list of flags like "fr,Germany,🇯🇵", see `resolve` for what's accepted.
+    pub fn parse_ids(list: &str) -> Vec<String> {
+        list.split(',')
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .collect()
+    }
+
+    pub fn parse_regions(list: &str) -> Result<Vec<Region>, String> {
+        list.split(',')
+            .map(|region| region.trim().parse())
+            .collect()
+    }
+
+    pub fn matches(&self, entry: &FlagEntry) -> bool {
+        let id = entry.id.to_lowercase();
+        (self.only.is_empty() || self.only.contains(&id))
+            && !self.exclude.contains(&id)
+            && (self.regions.is_empty()
+                || entry
+                    .country
+                    .is_some_and(|country| self.regions.contains(&country.region)))
+            && self.status.is_none_or(|status| {
+                entry
+                    .country
+                    .is_some_and(|country| country.status == status)
+            })
+    }
+
+    /// Keeps the matching entries, rejecting flags in `only`/`exclude` that aren't in `entries` at all.
+    pub fn apply(mut self, entries: Vec<FlagEntry>) -> Result<Vec<FlagEntry>, String> {
+        self.only = canonical_ids(&self.only, &entries)?;
+        self.exclude = canonical_ids(&self.exclude, &entries)?;
+
+        Ok(entries
+            .into_iter()
+            .filter(|entry| self.matches(entry))
+            .collect())
+    }
+}
+
+fn canonical_ids(ids: &[String], entries: &[FlagEntry]) -> Result<Vec<String>, String> {
+    // ids from a user dataset win, anything else goes through the registry resolver
+    ids.iter()
+        .map(|id| {
+            let id = id.to_lowercase();
+            if entries.iter().any(|entry| entry.id.to_lowercase() == id) {
+                return Ok(id);
+            }
+            let country = resolve(&id).map_err(|err| err.to_string())?;
+            if entries.iter().any(|entry| entry.id == country.code) {
+                Ok(country.code.to_string())
+            } else {
+                Err(format!(
+                    "{} ({}) isn't in the selected datasets",
+                    country.name, country.code
+                ))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flag_symmetry::{lookup, FlagSource};
+    use std::path::PathBuf;
+
+    fn entries() -> Vec<FlagEntry> {
+        let mut entries: Vec<_> = ["fr", "gl", "jp", "gb-sct"]
+            .iter()
+            .map(|code| FlagEntry::from_country(lookup(code).unwrap()))
+            .collect();
+        // a flag from a user dataset, with no registry metadata
+        entries.push(FlagEntry {
+            id: "Atlantis".to_string(),
+            name: "Atlantis".to_string(),
+            emoji: "🏳️".to_string(),
+            source: FlagSource::Path(PathBuf::from("atlantis.png")),
+            country: None,
+        });
+        entries
+    }
+
+    fn ids(filter: CountryFilter) -> Result<Vec<String>, String> {
+        Ok(filter
+            .apply(entries())?
+            .into_iter()
+            .map(|entry| entry.id)
+            .collect())
+    }
+
+    #[test]
+    fn no_restrictions_keep_everything() {
+        assert_eq!(
+            ids(CountryFilter::default()).unwrap(),
+            ["fr", "gl", "jp", "gb-sct", "Atlantis"]
+        );
+    }
+
+    #[test]
+    fn regions_keep_registry_flags_in_any_of_them() {
+        let filter = CountryFilter {
+            regions: CountryFilter::parse_regions("europe, americas").unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(ids(filter).unwrap(), ["fr", "gl", "gb-sct"]);
+        assert!(CountryFilter::parse_regions("europe,atlantic").is_err());
+    }
+
+    #[test]
+    fn status_keeps_registry_flags_with_it() {
+        let filter = CountryFilter {
+            status: Some(Status::Territory),
+            ..Default::default()
+        };
+        assert_eq!(ids(filter).unwrap(), ["gl"]);
+    }
+
+    #[test]
+    fn dataset_flags_are_selected_by_their_own_id() {
+        let filter = CountryFilter {
+            only: CountryFilter::parse_ids("atlantis, France"),
+            ..Default::default()
+        };
+        assert_eq!(ids(filter).unwrap(), ["fr", "Atlantis"]);
+    }
+
+    #[test]
+    fn exclude_wins_over_only() {
+        let filter = CountryFilter {
+            only: CountryFilter::parse_ids("fr,Greenland,🇯🇵,"),
+            exclude: CountryFilter::parse_ids("jp,atlantis"),
+            ..Default::default()
+        };
+        assert_eq!(ids(filter).unwrap(), ["fr", "gl"]);
+    }
+
+    #[test]
+    fn unknown_ids_are_rejected() {
+        let filter = CountryFilter {
+            only: vec!["Narnia".to_string()],
+            ..Default::default()
+        };
+        assert!(ids(filter).is_err());
+
+        let filter = CountryFilter {
+            exclude: vec!["Germany".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(filter).unwrap_err(),
+            "Germany (de) isn't in the selected datasets"
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::{Path, PathBuf};
+
+use crate::consts::countries::Country;
+
+// shown for flags without a dedicated emoji
+pub const FALLBACK_EMOJI: &str = "🏳️";
+
+/// Where a flag's image comes from.
+#[derive(Debug, Clone)]
+pub enum FlagSource {
+    Registry, // fetched by code through the fetcher's URL template
+    Url(String),
+    Path(PathBuf),
+}
+
+/// One flag to analyse, either from the built-in registry or a user-supplied dataset.
+#[derive(Debug, Clone)]
+pub struct FlagEntry {
+    pub id: String,
+    pub name: String,
+    pub emoji: String,
+    pub source: FlagSource,
+    pub country: Option<&'static Country>, // registry metadata, if the id is a known code
+}
+
+impl FlagEntry {
+    pub fn from_country(country: &'static Country) -> FlagEntry {
+        FlagEntry {
+            id: country.code.to_string(),
+            name: country.name.to_string(),
+            emoji: country.emoji.to_string(),
+            source: FlagSource::Registry,
+            country: Some(country),
+        }
+    }
+
+    /// A local image that isn't tied to any registry flag.
+    pub fn from_path(path: &Path) -> FlagEntry {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        FlagEntry {
+            id: path.display().to_string(),
+            name,
+            emoji: FALLBACK_EMOJI.to_string(),
+            source: FlagSource::Path(path.to_path_buf()),
+            country: None,
+        }
+    }
+}
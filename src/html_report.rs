@@ -0,0 +1,247 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::{DynamicImage, ImageFormat};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Cursor, Write};
+
+use flag_symmetry::analysis::{FlagResult, SymmetryClass};
+use flag_symmetry::symmetry::asymmetry_heatmap;
+
+const THUMBNAIL_WIDTH: u32 = 160;
+const THUMBNAIL_HEIGHT: u32 = 80;
+const CUSTOM_REGION: &str = "Custom"; // flags without registry metadata, as in the summary tables
+
+struct Card {
+    name: String,
+    emoji: String,
+    region: String,
+    scores: [f32; 3],
+    thumbnail: String,
+    heatmap: Option<String>, // left out for flags with full symmetry, there's nothing to show
+}
+
+/// A single static HTML page showing every analysed flag, grouped by symmetry class and
+/// filterable by class and region in the browser. Images are embedded as data URIs so the
+/// file can be passed around on its own.
+#[derive(Default)]
+pub struct HtmlReport {
+    cards: BTreeMap<SymmetryClass, Vec<Card>>,
+}
+
+impl HtmlReport {
+    pub fn new() -> HtmlReport {
+        HtmlReport::default()
+    }
+
+    /// Adds a flag, its thumbnails are encoded straight away so the image can be dropped.
+    pub fn add(&mut self, result: &FlagResult) -> Result<(), image::ImageError> {
+        let class = result.class();
+        let thumbnail = result.image.thumbnail(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT);
+        let heatmap = match class {
+            SymmetryClass::Full => None,
+            _ => Some(png_data_uri(&DynamicImage::ImageRgba8(asymmetry_heatmap(
+                &thumbnail,
+            )))?),
+        };
+        let region = match result.entry.country {
+            Some(country) => country.region.to_string(),
+            None => CUSTOM_REGION.to_string(),
+        };
+
+        self.cards.entry(class).or_default().push(Card {
+            name: result.entry.name.clone(),
+            emoji: result.entry.emoji.clone(),
+            region,
+            scores: [
+                result.scores.horizontal,
+                result.scores.vertical,
+                result.scores.rotational,
+            ],
+            thumbnail: png_data_uri(&thumbnail)?,
+            heatmap,
+        });
+        Ok(())
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        let regions: BTreeSet<&str> = self
+            .cards
+            .values()
+            .flatten()
+            .map(|card| card.region.as_str())
+            .collect();
+
+        writeln!(writer, "<!DOCTYPE html>")?;
+        writeln!(
+            writer,
+            "<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">"
+        )?;
+        writeln!(
+            writer,
+            "<title>Flag symmetry</title>\n<style>{}</style>",
+            STYLE
+        )?;
+        writeln!(writer, "</head>\n<body>\n<h1>Flag symmetry</h1>")?;
+
+        writeln!(writer, "<form id=\"filters\">")?;
+        writeln!(writer, "<label>Category <select id=\"class\">")?;
+        writeln!(writer, "<option value=\"\">All</option>")?;
+        for class in SymmetryClass::ALL {
+            writeln!(
+                writer,
+                "<option value=\"{}\">{}</option>",
+                class.label(),
+                title(class)
+            )?;
+        }
+        writeln!(writer, "</select></label>")?;
+        writeln!(writer, "<label>Region <select id=\"region\">")?;
+        writeln!(writer, "<option value=\"\">All</option>")?;
+        for region in &regions {
+            writeln!(writer, "<option>{}</option>", escape(region))?;
+        }
+        writeln!(writer, "</select></label>\n</form>")?;
+
+        for (class, cards) in &self.cards {
+            writeln!(writer, "<section data-class=\"{}\">", class.label())?;
+            writeln!(writer, "<h2>{} ({})</h2>", title(*class), cards.len())?;
+            writeln!(writer, "<div class=\"cards\">")?;
+            for card in cards {
+                write_card(&mut writer, card)?;
+            }
+            writeln!(writer, "</div>\n</section>")?;
+        }
+
+        writeln!(writer, "<script>{}</script>", SCRIPT)?;
+        writeln!(writer, "</body>\n</html>")
+    }
+}
+
+fn write_card(writer: &mut impl Write, card: &Card) -> io::Result<()> {
+    let name = escape(&card.name);
+    let [horizontal, vertical, rotational] = card.scores.map(|score| score * 100.0);
+
+    writeln!(writer, "<figure data-region=\"{}\">", escape(&card.region))?;
+    writeln!(
+        writer,
+        "<img src=\"{}\" alt=\"{}\" title=\"{}\">",
+        card.thumbnail, name, name
+    )?;
+    if let Some(heatmap) = &card.heatmap {
+        writeln!(
+            writer,
+            "<img src=\"{}\" alt=\"Asymmetry of {}\" title=\"Pixels that break a symmetry\">",
+            heatmap, name
+        )?;
+    }
+    writeln!(
+        writer,
+        "<figcaption>{} {}<br>↕️ {:.1}% ↔️ {:.1}% 🔄 {:.1}%</figcaption>",
+        escape(&card.emoji),
+        name,
+        horizontal,
+        vertical,
+        rotational
+    )?;
+    writeln!(writer, "</figure>")
+}
+
+fn title(class: SymmetryClass) -> &'static str {
+    match class {
+        SymmetryClass::Full => "🪩 Full symmetry",
+        SymmetryClass::Horizontal => "↕️ Horizontal line symmetry",
+        SymmetryClass::Vertical => "↔️ Vertical line symmetry",
+        SymmetryClass::None => "❌ No symmetry",
+    }
+}
+
+fn png_data_uri(img: &DynamicImage) -> Result<String, image::ImageError> {
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+#filters label { margin-right: 1em; }
+.cards { display: flex; flex-wrap: wrap; gap: 1em; }
+figure { margin: 0; padding: 0.5em; border: 1px solid #ddd; border-radius: 4px; text-align: center; }
+figure img { display: block; margin: 0 auto 0.25em; border: 1px solid #eee; }
+figcaption { font-size: 0.85em; }
+[hidden] { display: none !important; }
+";
+
+// hides the cards that don't match, then any section left without a visible card
+const SCRIPT: &str = "
+const classFilter = document.getElementById('class');
+const regionFilter = document.getElementById('region');
+function applyFilters() {
+  for (const section of document.querySelectorAll('section')) {
+    let visible = 0;
+    for (const card of section.querySelectorAll('figure')) {
+      card.hidden = (classFilter.value && section.dataset.class !== classFilter.value)
+        || (regionFilter.value && card.dataset.region !== regionFilter.value);
+      if (!card.hidden) visible++;
+    }
+    section.hidden = visible === 0;
+  }
+}
+classFilter.addEventListener('change', applyFilters);
+regionFilter.addEventListener('change', applyFilters);
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flag_symmetry::consts::countries::lookup;
+    use flag_symmetry::flags::FlagEntry;
+    use flag_symmetry::symmetry::{check_symmetry, symmetry_scores};
+    use image::{Rgba, RgbaImage};
+
+    fn result(code: &str, img: RgbaImage) -> FlagResult {
+        let image = DynamicImage::ImageRgba8(img);
+        FlagResult {
+            entry: FlagEntry::from_country(lookup(code).unwrap()),
+            source: format!("{}.png", code),
+            width: image.width(),
+            height: image.height(),
+            symmetry: check_symmetry(&image),
+            scores: symmetry_scores(&image),
+            palette: Vec::new(),
+            vector_symmetry: None,
+            image,
+        }
+    }
+
+    #[test]
+    fn groups_flags_and_only_maps_asymmetric_ones() {
+        let japan = RgbaImage::from_pixel(30, 20, Rgba([255, 255, 255, 255]));
+        let monaco = RgbaImage::from_fn(30, 20, |_, y| {
+            if y < 10 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        });
+        let mut report = HtmlReport::new();
+        report.add(&result("jp", japan)).unwrap();
+        report.add(&result("mc", monaco)).unwrap();
+
+        let mut html = Vec::new();
+        report.write(&mut html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+
+        assert!(
+            html.find("data-class=\"Full\"").unwrap() < html.find("data-class=\"Vert\"").unwrap()
+        );
+        assert_eq!(html.matches("alt=\"Asymmetry of").count(), 1);
+        assert!(html.contains("<option>Asia</option>") && html.contains("<option>Europe</option>"));
+    }
+}
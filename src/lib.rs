@@ -0,0 +1,61 @@
+//! Reports the line and rotational symmetry of national flags.
+//!
+//! The analysis works on any decoded image, so it can be used without touching the network:
+//!
+//! ```
+//! use flag_symmetry::{check_symmetry, Symmetry};
+//! use image::{DynamicImage, Rgba, RgbaImage};
+//!
+//! // a France-like tricolour: blue, white, red stripes
+//! let flag = RgbaImage::from_fn(30, 20, |x, _| match x / 10 {
+//!     0 => Rgba([0, 85, 164, 255]),
+//!     1 => Rgba([255, 255, 255, 255]),
+//!     _ => Rgba([239, 65, 53, 255]),
+//! });
+//!
+//! let symmetry = check_symmetry(&DynamicImage::ImageRgba8(flag));
+//! assert_eq!(
+//!     symmetry,
+//!     Symmetry { horizontal: true, vertical: false, rotational: false }
+//! );
+//! ```
+//!
+//! [`FlagFetcher`] downloads flags from flagcdn (or a mirror) with rate limiting and retries,
+//! [`analyse_flags`] runs the analysis over a stream of downloads, [`resolve()`] and [`countries`]
+//! find flags in the registry, and [`results`] reads and writes the results files.
+//!
+//! The `contact-sheet` feature (on by default) adds [`contact_sheet`], which embeds a font to
+//! label the flags.
+pub mod analysis;
+pub mod atomic_file;
+#[cfg(feature = "contact-sheet")]
+pub mod contact_sheet;
+pub mod fetch_flags;
+pub mod flags;
+pub mod locale;
+pub mod manifest;
+pub mod resolve;
+pub mod results;
+pub mod symmetry;
+pub mod url_template;
+pub mod consts {
+    pub mod countries;
+}
+
+pub(crate) mod palette;
+#[cfg(test)]
+mod test_server;
+pub(crate) mod vector_symmetry;
+
+pub use analysis::{analyse_flags, FlagResult, SymmetryClass};
+pub use consts::countries;
+pub use consts::countries::{lookup, Country};
+pub use fetch_flags::{ClientConfig, FetchError, FlagFetcher};
+pub use flags::{FlagEntry, FlagSource};
+pub use locale::{localize, Lang};
+pub use manifest::Manifest;
+pub use palette::PaletteColor;
+pub use resolve::resolve;
+pub use results::{read_results, FlagReport, ResultsWriter};
+pub use symmetry::{check_symmetry, symmetry_scores, Symmetry, SymmetryScores};
+pub use url_template::UrlTemplate;
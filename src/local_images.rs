@@ -0,0 +1,160 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use flag_symmetry::fetch_flags::is_supported_image;
+
+#[derive(Debug)]
+pub enum ImagePathError {
+    Pattern(glob::PatternError),
+    NoMatches(String), // an input that is neither an existing path nor a pattern matching any image
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ImagePathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImagePathError::Pattern(err) => write!(f, "invalid glob pattern: {}", err),
+            ImagePathError::NoMatches(input) => write!(f, "no images found for '{}'", input),
+            ImagePathError::Io(err) => write!(f, "failed to list images: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ImagePathError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImagePathError::Pattern(err) => Some(err),
+            ImagePathError::NoMatches(_) => None,
+            ImagePathError::Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<glob::PatternError> for ImagePathError {
+    fn from(err: glob::PatternError) -> Self {
+        ImagePathError::Pattern(err)
+    }
+}
+
+impl From<std::io::Error> for ImagePathError {
+    fn from(err: std::io::Error) -> Self {
+        ImagePathError::Io(err)
+    }
+}
+
+/// Turns files, directories and glob patterns like "mockups/*.png" into a list of image files.
+/// Files are taken as given, directories and patterns only contribute images in a supported format.
+/// Directories aren't searched recursively, use a "**" pattern for that.
+pub fn expand_image_paths(inputs: &[String]) -> Result<Vec<PathBuf>, ImagePathError> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+        let found = if path.is_file() {
+            vec![path.to_path_buf()]
+        } else if path.is_dir() {
+            let mut images = Vec::new();
+            for entry in std::fs::read_dir(path)? {
+                let entry_path = entry?.path();
+                if entry_path.is_file() && is_supported_image(&entry_path) {
+                    images.push(entry_path);
+                }
+            }
+            images.sort();
+            images
+        } else {
+            glob::glob(input)?
+                .filter_map(Result::ok)
+                .filter(|path| path.is_file() && is_supported_image(path))
+                .collect()
+        };
+
+        if found.is_empty() {
+            return Err(ImagePathError::NoMatches(input.clone()));
+        }
+        // the same file can come up through more than one input
+        for path in found {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::{tempdir, TempDir};
+
+    // a fresh directory with a.png, b.jpg, notes.txt and sub/c.png, the files don't need to be valid images
+    fn image_dir() -> TempDir {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        for file in ["a.png", "b.jpg", "notes.txt", "sub/c.png"] {
+            std::fs::write(dir.path().join(file), b"").unwrap();
+        }
+        dir
+    }
+
+    fn input(path: &Path) -> String {
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn directories_contribute_their_images() {
+        let temp = image_dir();
+        let dir = temp.path();
+
+        let paths = expand_image_paths(&[input(dir)]).unwrap();
+
+        assert_eq!(paths, vec![dir.join("a.png"), dir.join("b.jpg")]);
+    }
+
+    #[test]
+    fn globs_match_images_only() {
+        let temp = image_dir();
+        let dir = temp.path();
+
+        let flat = expand_image_paths(&[input(&dir.join("*"))]).unwrap();
+        let recursive = expand_image_paths(&[input(&dir.join("**").join("*.png"))]).unwrap();
+        let err = expand_image_paths(&[input(&dir.join("*.txt"))]).unwrap_err();
+
+        assert_eq!(flat, vec![dir.join("a.png"), dir.join("b.jpg")]);
+        assert_eq!(
+            recursive,
+            vec![dir.join("a.png"), dir.join("sub").join("c.png")]
+        );
+        assert!(matches!(err, ImagePathError::NoMatches(_)));
+    }
+
+    #[test]
+    fn files_are_taken_as_given_once() {
+        let temp = image_dir();
+        let dir = temp.path();
+
+        let paths = expand_image_paths(&[
+            input(&dir.join("notes.txt")),
+            input(&dir.join("a.png")),
+            input(dir),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            paths,
+            vec![dir.join("notes.txt"), dir.join("a.png"), dir.join("b.jpg")]
+        );
+    }
+
+    #[test]
+    fn missing_paths_are_an_error() {
+        let temp = image_dir();
+        let dir = temp.path();
+        let missing = input(&dir.join("missing.png"));
+
+        let err = expand_image_paths(&[input(dir), missing.clone()]).unwrap_err();
+
+        assert!(matches!(err, ImagePathError::NoMatches(input) if input == missing));
+    }
+}
@@ -0,0 +1,143 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::flags::FlagEntry;
+
+// bundled translations of the registry names (code,name), generated from CLDR
+const FR_NAMES: &str = include_str!("../data/locales/fr.csv");
+const DE_NAMES: &str = include_str!("../data/locales/de.csv");
+const ES_NAMES: &str = include_str!("../data/locales/es.csv");
+const JA_NAMES: &str = include_str!("../data/locales/ja.csv");
+
+/// A language country names can be reported in.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    En,
+    Fr,
+    De,
+    Es,
+    Ja,
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "en" => Ok(Lang::En),
+            "fr" => Ok(Lang::Fr),
+            "de" => Ok(Lang::De),
+            "es" => Ok(Lang::Es),
+            "ja" => Ok(Lang::Ja),
+            _ => Err(format!(
+                "unsupported language '{}', expected one of en, fr, de, es, ja",
+                s
+            )),
+        }
+    }
+}
+
+/// Country names in one language, keyed by registry code.
+///
+/// ```
+/// use flag_symmetry::locale::{Lang, Translations};
+///
+/// assert_eq!(Translations::load(Lang::Ja).name("jp"), Some("日本"));
+/// assert_eq!(Translations::load(Lang::En).name("jp"), None); // English is the registry itself
+/// ```
+pub struct Translations(HashMap<String, String>);
+
+impl Translations {
+    pub fn load(lang: Lang) -> Translations {
+        let csv = match lang {
+            Lang::En => return Translations(HashMap::new()), // the registry is already in English
+            Lang::Fr => FR_NAMES,
+            Lang::De => DE_NAMES,
+            Lang::Es => ES_NAMES,
+            Lang::Ja => JA_NAMES,
+        };
+
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let names = reader
+            .records()
+            .map(|record| record.expect("bundled locale CSV is valid"))
+            .map(|record| (record[0].to_string(), record[1].to_string()))
+            .collect();
+        Translations(names)
+    }
+
+    pub fn name(&self, code: &str) -> Option<&str> {
+        self.0.get(code).map(|name| name.as_str())
+    }
+}
+
+/// Swaps registry names for their translation, falling back to English where there isn't one.
+/// Names given explicitly in a user dataset are left alone.
+///
+/// ```
+/// use flag_symmetry::{localize, lookup, FlagEntry, Lang};
+///
+/// let mut entries = [FlagEntry::from_country(lookup("de").unwrap())];
+/// localize(&mut entries, Lang::Fr);
+/// assert_eq!(entries[0].name, "Allemagne");
+/// ```
+pub fn localize(entries: &mut [FlagEntry], lang: Lang) {
+    let translations = Translations::load(lang);
+    for entry in entries {
+        let Some(country) = entry.country else {
+            continue;
+        };
+        if entry.name != country.name {
+            continue;
+        }
+        if let Some(name) = translations.name(country.code) {
+            entry.name = name.to_string();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::countries::lookup;
+
+    fn localized(code: &str, lang: Lang) -> String {
+        let mut entries = [FlagEntry::from_country(lookup(code).unwrap())];
+        localize(&mut entries, lang);
+        entries[0].name.clone()
+    }
+
+    #[test]
+    fn registry_names_are_translated_in_every_language() {
+        assert_eq!(localized("de", Lang::En), "Germany");
+        assert_eq!(localized("de", Lang::Fr), "Allemagne");
+        assert_eq!(localized("de", Lang::De), "Deutschland");
+        assert_eq!(localized("de", Lang::Es), "Alemania");
+        assert_eq!(localized("de", Lang::Ja), "ドイツ");
+    }
+
+    #[test]
+    fn missing_translations_fall_back_to_english() {
+        assert_eq!(Translations::load(Lang::Fr).name("us-ca"), None);
+        assert_eq!(localized("us-ca", Lang::Fr), "California");
+    }
+
+    #[test]
+    fn dataset_names_are_left_alone() {
+        let mut entries = [FlagEntry::from_country(lookup("de").unwrap())];
+        entries[0].name = "West Germany".to_string();
+
+        localize(&mut entries, Lang::Fr);
+
+        assert_eq!(entries[0].name, "West Germany");
+    }
+
+    #[test]
+    fn languages_parse_case_insensitively() {
+        assert_eq!("JA".parse::<Lang>(), Ok(Lang::Ja));
+        assert!("pt".parse::<Lang>().is_err());
+    }
+}
@@ -1,120 +1,1032 @@
-/// A simple app that reports the symmetry of a flag.
-/// This only focuses on symmetry along the vertical axis.
-use image::DynamicImage;
-mod fetch_flags;
-mod symmetry;
-mod consts {
-    pub mod countries;
-}
-
-use fetch_flags::get_flag;
+//! The command line tool: parses the arguments, selects and analyses flags with the
+//! `flag_symmetry` library, and prints or writes the horizontal, vertical and rotational results.
+use clap::Parser;
+use flag_symmetry::analysis::{analyse_flags, flag_source, AnalysisError, SymmetryClass};
+use flag_symmetry::atomic_file::{AtomicFile, OutputTarget};
+use flag_symmetry::consts::countries::{
+    self, name_sort_key, sorted_countries, Country, CountryOrder, Region, Status, DEFAULT_DATASETS,
+};
+use flag_symmetry::contact_sheet::{contact_sheet, sheet_label, SheetLayout};
+use flag_symmetry::fetch_flags::{
+    load_flag_bytes, load_flag_file, ClientConfig, FetchError, FlagFetcher,
+};
+use flag_symmetry::flags::{FlagEntry, FlagSource, FALLBACK_EMOJI};
+use flag_symmetry::locale::localize;
+use flag_symmetry::manifest::Manifest;
+use flag_symmetry::resolve::resolve;
+use flag_symmetry::results::{diff_results, read_results, FlagReport, ResultsWriter, RunMetadata};
+use flag_symmetry::symmetry::{check_symmetry, Axis};
+use futures::stream::{self, StreamExt};
+use image::{DynamicImage, ImageFormat};
 use prettytable::{row, Table};
-use symmetry::check_symmetry;
 
 use indicatif::ProgressBar;
 
-use csv::Writer;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+mod cli;
+// only the command line tool needs these, they aren't part of the library
+mod filter;
+mod html_report;
+mod local_images;
+mod user_dataset;
+
+use cli::{
+    CheckArgs, Cli, Command, DiffArgs, DownloadOptions, FetchArgs, FilesArgs, ReportArgs,
+    ReportFormat, RunArgs, Selection, SummaryOptions, DEFAULT_RESULTS_STEM,
+};
+use filter::CountryFilter;
+use html_report::HtmlReport;
+use local_images::expand_image_paths;
+use user_dataset::load_dataset;
+
+const SUMMARY_PAUSE: Duration = Duration::from_secs(5);
+const SUMMARY_START: &str = "<!-- summary:start -->";
+const SUMMARY_END: &str = "<!-- summary:end -->";
+
+// names can contain commas, e.g. "Saint Helena, Ascension and Tristan da Cunha"
+const SUMMARY_SEPARATOR: &str = "; ";
+
+/// A flag as listed in the summary.
+struct SummaryFlag {
+    code: String, // empty for results files that predate the code column and names that don't resolve
+    name: String,
+    emoji: String,
+}
+
+impl SummaryFlag {
+    fn label(&self, with_emoji: bool) -> String {
+        let mut label = if with_emoji {
+            format!("{} {}", self.emoji, self.name)
+        } else {
+            self.name.clone()
+        };
+        if !self.code.is_empty() {
+            label.push_str(&format!(" ({})", self.code));
+        }
+        label
+    }
+}
+
+/// Running totals for the summary tables.
+#[derive(Default)]
+struct Totals {
+    by_class: BTreeMap<SymmetryClass, Vec<SummaryFlag>>,
+    by_region: BTreeMap<Option<Region>, [i64; 4]>,
+    by_status: BTreeMap<Option<(Status, bool)>, [i64; 4]>,
+}
 
-const CHUNK_SIZE: usize = 126;
-const TIME_DELAY: u64 = 1;
+impl Totals {
+    fn add(&mut self, flag: SummaryFlag, country: Option<&Country>, class: SymmetryClass) {
+        self.by_class.entry(class).or_default().push(flag);
 
-#[derive(Debug)]
-struct ReportingValue(i64, String);
+        let category = class as usize;
+        self.by_region
+            .entry(country.map(|country| country.region))
+            .or_default()[category] += 1;
+        self.by_status
+            .entry(country.map(|country| (country.status, country.un_member)))
+            .or_default()[category] += 1;
+    }
+
+    fn count(&self, class: SymmetryClass) -> usize {
+        self.by_class.get(&class).map_or(0, Vec::len)
+    }
+
+    /// The flags in `class`, ordered and labelled as asked for.
+    fn list(&self, class: SymmetryClass, options: &SummaryOptions) -> String {
+        let mut flags: Vec<&SummaryFlag> =
+            self.by_class.get(&class).into_iter().flatten().collect();
+        match options.summary_sort {
+            Some(CountryOrder::Code) => {
+                flags.sort_by(|a, b| a.code.cmp(&b.code).then(a.name.cmp(&b.name)))
+            }
+            Some(CountryOrder::Name) => {
+                flags.sort_by_cached_key(|flag| (name_sort_key(&flag.name), flag.code.clone()))
+            }
+            None => {}
+        }
+        flags
+            .iter()
+            .map(|flag| flag.label(options.summary_emoji))
+            .collect::<Vec<_>>()
+            .join(SUMMARY_SEPARATOR)
+    }
+}
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut total_horizontal = ReportingValue(0, String::new());
-    let mut total_vertical = ReportingValue(0, String::new());
-    let mut total_full = ReportingValue(0, String::new());
-    let mut total_none = ReportingValue(0, String::new());
-
-    // Create a CSV file to store the results
-    let mut result_csv = Writer::from_path("results.csv")?;
-    result_csv.write_record(&["Flag", "Country name", "Line of symmetry"])?;
-
-    // Loop through all countries, but chunk into groups to avoid hitting rate limits
-    let countries = consts::countries::COUNTRIES
-        .iter()
-        .map(|(code, (name, emoji))| (code.to_string(), (name.to_string(), emoji.to_string())))
-        .collect::<Vec<(String, (String, String))>>();
+async fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Run(args) => run(args).await,
+        Command::Check(args) if args.flag == "-" => return check_stdin(args.axis),
+        Command::Check(args) => check(args).await,
+        Command::Files(args) => files(args),
+        Command::Fetch(args) => fetch(args).await,
+        Command::Report(args) => report(args).await,
+        Command::Diff(args) => diff(args),
+    };
+    // returned rather than exiting so open writers are dropped and flushed first
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: RunArgs) -> Result<(), Box<dyn Error>> {
+    let fetcher = build_fetcher(&args.download)?;
+    let entries = select_entries(&args.selection)?;
+    let mut totals = Totals::default();
+    let mut vector_mismatches: Vec<String> = Vec::new();
+    let mut failures: Vec<String> = Vec::new();
+
+    // Create a results file, see --format and --output. It only replaces an existing file once the run is done.
+    let path = args.output.clone().unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "{}.{}",
+            DEFAULT_RESULTS_STEM,
+            args.format.extension()
+        ))
+    });
+    let target = OutputTarget::open(&path, args.force).map_err(already_exists_hint)?;
+    let mut html = match &args.html {
+        Some(path) => Some((
+            AtomicFile::create(path, args.force).map_err(already_exists_hint)?,
+            HtmlReport::new(),
+        )),
+        None => None,
+    };
+    // flags are kept per category until the end of the run, only when contact sheets were asked for
+    let mut sheet_flags: BTreeMap<SymmetryClass, Vec<(String, DynamicImage)>> = BTreeMap::new();
+    // with results on stdout, the summary would get mixed into them
+    let show_summary = !target.is_stdout();
+    let mut output = ResultsWriter::new(target, args.format, RunMetadata::new(&args)?)?;
 
     // progress bar
-    println!("\n\nChecking flags for symmetry...");
-    let progress = ProgressBar::new(countries.iter().len() as u64);
+    if show_summary {
+        println!("\n\nChecking flags for symmetry...");
+    }
+    let progress = ProgressBar::new(entries.len() as u64);
 
-    let chunked_countries = countries
-        .chunks(CHUNK_SIZE)
-        .into_iter()
-        .collect::<Vec<&[(String, (String, String))]>>();
-
-    for chunk in chunked_countries {
-        // Sleep for a bit to avoid rate limiting
-        tokio::time::sleep(tokio::time::Duration::from_secs(TIME_DELAY)).await;
-        for (code, name) in chunk {
-            let img: DynamicImage = get_flag(code).await?;
-
-            let symmetry = check_symmetry(&img);
-            let sym_val: &str;
-
-            if symmetry.horizontal && symmetry.vertical {
-                update_table_total_value(&mut total_full, name);
-                sym_val = "Full";
-            } else if symmetry.vertical {
-                update_table_total_value(&mut total_vertical, name);
-                sym_val = "Vert";
-            } else if symmetry.horizontal {
-                update_table_total_value(&mut total_horizontal, name);
-                sym_val = "Horz";
-            } else {
-                update_table_total_value(&mut total_none, name);
-                sym_val = "None";
+    // results come back in the original order, so the CSV and summary don't depend on which request finished first
+    let mut results = std::pin::pin!(analyse_flags(
+        &fetcher,
+        &entries,
+        args.download.concurrency,
+        args.vector
+    ));
+
+    while let Some((entry, result)) = results.next().await {
+        progress.inc(1);
+        // a flag that can't be fetched or decoded is reported, but doesn't stop the run
+        let result = match result {
+            Ok(result) => result,
+            Err(AnalysisError::Fetch(err @ FetchError::ManifestMismatch(_))) => {
+                return Err(manifest_mismatch(entry, &err));
             }
+            Err(err) => {
+                failures.push(format!("{} {}: {}", entry.emoji, entry.name, err));
+                output.write(FlagReport::from_error(
+                    entry,
+                    flag_source(&fetcher, entry),
+                    &err,
+                ))?;
+                continue;
+            }
+        };
 
-            // Write to CSV
-            result_csv.write_record(&[name.1.as_str(), name.0.as_str(), sym_val])?;
-            progress.inc(1);
+        if result.vector_mismatch() {
+            vector_mismatches.push(format!("{} {}", entry.emoji, entry.name));
+        }
+        let flag = SummaryFlag {
+            code: entry.id.clone(),
+            name: entry.name.clone(),
+            emoji: entry.emoji.clone(),
+        };
+        totals.add(flag, entry.country, result.class());
+        output.write(FlagReport::from_result(&result))?;
+        if let Some((_, report)) = &mut html {
+            report.add(&result)?;
+        }
+        if args.contact_sheets.is_some() {
+            let class = result.class();
+            sheet_flags
+                .entry(class)
+                .or_default()
+                .push((sheet_label(&result.entry), result.image));
         }
     }
 
-    result_csv.flush()?;
+    output.finish()?.commit()?;
+    if let Some((mut file, report)) = html {
+        report.write(&mut file)?;
+        file.commit()?;
+    }
+    if let Some(dir) = &args.contact_sheets {
+        let layout = SheetLayout {
+            columns: args.sheet_columns,
+            padding: args.sheet_padding,
+            ..SheetLayout::default()
+        };
+        write_contact_sheets(dir, &sheet_flags, &layout, args.force)?;
+    }
     progress.finish_with_message("done");
-    report_result_summary(total_full, total_horizontal, total_vertical, total_none).await;
+    save_manifest(&fetcher)?;
+    if !show_summary {
+        return report_failures("analyse", &failures);
+    }
+
+    report_result_summary(&totals, &args.summary, SUMMARY_PAUSE).await;
+    report_group_summaries(&totals);
+    report_download_stats(&fetcher);
+
+    if args.vector {
+        println!("\n");
+        println!(
+            "Raster and vector analysis disagree on {} flags: {}",
+            vector_mismatches.len(),
+            vector_mismatches.join(", ")
+        );
+    }
+
+    report_failures("analyse", &failures)
+}
+
+async fn check(args: CheckArgs) -> Result<(), Box<dyn Error>> {
+    let fetcher = build_fetcher(&args.download)?;
+
+    // an existing file wins over a flag that happens to have the same name
+    let path = Path::new(&args.flag);
+    let entry = if path.is_file() {
+        FlagEntry::from_path(path)
+    } else {
+        FlagEntry::from_country(resolve(&args.flag)?)
+    };
+
+    let entries = [entry];
+    let mut results = std::pin::pin!(analyse_flags(&fetcher, &entries, 1, args.vector));
+    let Some((_, result)) = results.next().await else {
+        return Ok(());
+    };
+    let result = result?;
+
+    let mut table = Table::new();
+    table.add_row(row![
+        "Flag",
+        format!("{} {}", result.entry.emoji, result.entry.name)
+    ]);
+    if let Some(country) = result.entry.country {
+        table.add_row(row!["Code", country.code]);
+    }
+    table.add_row(row![
+        "↕️ Horizontal line symmetry",
+        yes_no(result.symmetry.horizontal)
+    ]);
+    table.add_row(row![
+        "↔️ Vertical line symmetry",
+        yes_no(result.symmetry.vertical)
+    ]);
+    table.add_row(row![
+        "🔄 Rotational symmetry",
+        yes_no(result.symmetry.rotational)
+    ]);
+    table.add_row(row!["Category", result.class().label()]);
+    if let Some(vector) = &result.vector_symmetry {
+        let agreement = if result.vector_mismatch() {
+            format!("disagrees ({})", SymmetryClass::of(vector).label())
+        } else {
+            "agrees".to_string()
+        };
+        table.add_row(row!["Vector analysis", agreement]);
+    }
+    table.printstd();
     Ok(())
 }
 
-fn update_table_total_value(table_total: &mut ReportingValue, name: &(String, String)) {
-    table_total.0 += 1;
-    table_total.1.push_str(name.1.as_str());
+fn check_stdin(axis: Axis) -> ExitCode {
+    // for pipelines: JSON on stdout, exit 0 if the image has `axis`, 1 if it doesn't and 2 on any error
+    let mut bytes = Vec::new();
+    let (output, code) = match std::io::stdin().read_to_end(&mut bytes) {
+        Ok(_) => check_image_bytes(&bytes, axis),
+        Err(err) => check_error(&FetchError::from(err)),
+    };
+    println!("{}", output);
+    ExitCode::from(code)
 }
 
-async fn report_result_summary(
-    total_full: ReportingValue,
-    total_horizontal: ReportingValue,
-    total_vertical: ReportingValue,
-    total_none: ReportingValue,
-) {
+/// The JSON `check -` prints for an image, and the exit code that goes with it.
+fn check_image_bytes(bytes: &[u8], axis: Axis) -> (serde_json::Value, u8) {
+    let img = match load_flag_bytes(bytes) {
+        Ok(img) => img,
+        Err(err) => return check_error(&err),
+    };
+    // a bug in the analysis still has to end in exit code 2 rather than a panic
+    let symmetry = match std::panic::catch_unwind(|| check_symmetry(&img)) {
+        Ok(symmetry) => symmetry,
+        Err(panic) => {
+            let reason = panic
+                .downcast_ref::<&str>()
+                .map(|reason| reason.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            return check_error(&format!("analysis failed: {}", reason));
+        }
+    };
+
+    let symmetric = symmetry.has(axis);
+    let output = serde_json::json!({
+        "source": "-",
+        "width": img.width(),
+        "height": img.height(),
+        "horizontal": symmetry.horizontal,
+        "vertical": symmetry.vertical,
+        "rotational": symmetry.rotational,
+        "class": SymmetryClass::of(&symmetry),
+        "axis": axis,
+        "symmetric": symmetric,
+    });
+    (output, if symmetric { 0 } else { 1 })
+}
+
+fn check_error(err: &dyn std::fmt::Display) -> (serde_json::Value, u8) {
+    eprintln!("error: {}", err);
+    (
+        serde_json::json!({ "source": "-", "error": err.to_string() }),
+        2,
+    )
+}
+
+fn files(args: FilesArgs) -> Result<(), Box<dyn Error>> {
+    let paths = expand_image_paths(&args.paths)?;
+
+    // an unreadable file is reported in its row rather than stopping the others
     let mut table = Table::new();
+    table.add_row(row![
+        "File",
+        "↕️ Horizontal",
+        "↔️ Vertical",
+        "🔄 Rotational",
+        "Category"
+    ]);
+    let mut failures = 0;
+    for path in &paths {
+        match load_flag_file(path) {
+            Ok(img) => {
+                let symmetry = check_symmetry(&img);
+                table.add_row(row![
+                    path.display(),
+                    yes_no(symmetry.horizontal),
+                    yes_no(symmetry.vertical),
+                    yes_no(symmetry.rotational),
+                    SymmetryClass::of(&symmetry).label()
+                ]);
+            }
+            Err(err) => {
+                failures += 1;
+                table.add_row(row![path.display(), H4 -> format!("error: {}", err)]);
+            }
+        }
+    }
+    table.printstd();
 
-    println!("\n\n\n");
-    println!("In summary: ");
-    table.add_row(row!["🪩 Flags with full symmetry", total_full.0]);
+    if failures > 0 {
+        return Err(format!(
+            "{} of {} images couldn't be analysed",
+            failures,
+            paths.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+async fn fetch(args: FetchArgs) -> Result<(), Box<dyn Error>> {
+    let Some(cache_dir) = args.download.cache() else {
+        return Err("fetch only fills the cache, it can't be used with --no-cache".into());
+    };
+    let fetcher = build_fetcher(&args.download)?;
+    let entries = select_entries(&args.selection)?;
+
+    println!("\n\nFetching flags into {}...", cache_dir.display());
+    let progress = ProgressBar::new(entries.len() as u64);
+
+    // keep going past failures so one bad flag doesn't stop the rest from being cached
+    let mut downloads = stream::iter(&entries)
+        .map(|entry| {
+            let fetcher = &fetcher;
+            async move { (entry, fetch_entry(fetcher, entry, args.vector).await) }
+        })
+        .buffered(args.download.concurrency.max(1));
+
+    let mut failures: Vec<String> = Vec::new();
+    while let Some((entry, result)) = downloads.next().await {
+        match result {
+            Ok(()) => {}
+            Err(err @ FetchError::ManifestMismatch(_)) => {
+                return Err(manifest_mismatch(entry, &err))
+            }
+            Err(err) => failures.push(format!("{} {}: {}", entry.emoji, entry.name, err)),
+        }
+        progress.inc(1);
+    }
+    progress.finish_with_message("done");
+
+    report_download_stats(&fetcher);
+    save_manifest(&fetcher)?;
+    report_failures("fetch", &failures)
+}
+
+async fn fetch_entry(
+    fetcher: &FlagFetcher,
+    entry: &FlagEntry,
+    vector: bool,
+) -> Result<(), FetchError> {
+    // local files don't need caching
+    match &entry.source {
+        FlagSource::Registry => {
+            fetcher.get_flag(&entry.id).await?;
+            if vector {
+                fetcher.get_flag_svg(&entry.id).await?;
+            }
+        }
+        FlagSource::Url(url) => {
+            fetcher.get_flag_from_url(&entry.id, url).await?;
+        }
+        FlagSource::Path(_) => {}
+    }
+    Ok(())
+}
+
+async fn report(args: ReportArgs) -> Result<(), Box<dyn Error>> {
+    let results = read_results(&args.results)?;
+    let mut totals = Totals::default();
+    for record in results.records {
+        let country = countries::lookup(&record.code);
+        let flag = SummaryFlag {
+            code: record.code,
+            name: record.name,
+            emoji: record.emoji,
+        };
+        totals.add(flag, country, record.class);
+    }
+
+    if args.format == ReportFormat::Text && args.readme.is_none() {
+        report_result_summary(&totals, &args.summary, Duration::ZERO).await;
+        report_group_summaries(&totals);
+        return Ok(());
+    }
+
+    let file_name = args
+        .results
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let mut summary = markdown_summary(
+        &totals,
+        &args.summary,
+        &file_name,
+        results.metadata.as_ref(),
+    );
+    match &args.readme {
+        Some(readme) => {
+            let text = std::fs::read_to_string(readme)?;
+            let updated = replace_summary_section(&text, &summary).ok_or_else(|| {
+                format!(
+                    "{} has no {} ... {} section",
+                    readme.display(),
+                    SUMMARY_START,
+                    SUMMARY_END
+                )
+            })?;
+            let mut file = AtomicFile::create(readme, true)?;
+            file.write_all(updated.as_bytes())?;
+            file.commit()?;
+        }
+        None => {
+            summary.push('\n');
+            print!("{}", summary);
+        }
+    }
+    Ok(())
+}
+
+/// The README's summary section: the totals table and flags per category, then where they came from.
+fn markdown_summary(
+    totals: &Totals,
+    options: &SummaryOptions,
+    results_file: &str,
+    metadata: Option<&RunMetadata>,
+) -> String {
+    let flags: usize = SymmetryClass::ALL
+        .iter()
+        .map(|class| totals.count(*class))
+        .sum();
+    let generated = match metadata {
+        Some(metadata) => format!(
+            "Generated by {} {} on {} from `{}` ({} flags).",
+            metadata.tool, metadata.version, metadata.generated_at, results_file, flags
+        ),
+        None => format!("Generated from `{}` ({} flags).", results_file, flags),
+    };
+
+    let mut lines = vec!["```".to_string()];
+    lines.extend(
+        summary_table(totals)
+            .to_string()
+            .lines()
+            .map(str::to_string),
+    );
+    lines.push("```".to_string());
+    for class in SymmetryClass::ALL {
+        lines.push(String::new());
+        lines.push(format!(
+            "**{} flags ({}):** {}",
+            class_title(class),
+            totals.count(class),
+            totals.list(class, options)
+        ));
+    }
+    if options.summary_emoji {
+        lines.push(String::new());
+        lines.push(format!(
+            "\\*flags that do not have an associated emoji are shown as {}",
+            FALLBACK_EMOJI
+        ));
+    }
+    lines.push(String::new());
+    lines.push(format!("<sub>{}</sub>", generated));
+    lines.join("\n")
+}
+
+fn replace_summary_section(text: &str, summary: &str) -> Option<String> {
+    let start = text.find(SUMMARY_START)? + SUMMARY_START.len();
+    let end = start + text[start..].find(SUMMARY_END)?;
+    Some(format!("{}\n{}\n{}", &text[..start], summary, &text[end..]))
+}
+
+fn diff(args: DiffArgs) -> Result<(), Box<dyn Error>> {
+    let old = read_results(&args.old)?;
+    let new = read_results(&args.new)?;
+    let changes = diff_results(&old.records, &new.records);
+
+    if changes.is_empty() {
+        println!("No differences");
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.add_row(row!["Flag", "Country name", "Before", "After"]);
+    let label = |class: Option<SymmetryClass>| class.map_or("-", SymmetryClass::label);
+    for change in &changes {
+        table.add_row(row![
+            change.record.emoji,
+            change.record.name,
+            label(change.old),
+            label(change.new)
+        ]);
+    }
+    table.printstd();
+    println!("{} flags changed", changes.len());
+    Ok(())
+}
+
+/// One PNG per symmetry category, categories without any flags are skipped.
+fn write_contact_sheets(
+    dir: &Path,
+    flags: &BTreeMap<SymmetryClass, Vec<(String, DynamicImage)>>,
+    layout: &SheetLayout,
+    overwrite: bool,
+) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+    for (class, flags) in flags {
+        let (title, file_name) = match class {
+            SymmetryClass::Full => ("Full symmetry", "full.png"),
+            SymmetryClass::Horizontal => ("Horizontal line symmetry", "horizontal.png"),
+            SymmetryClass::Vertical => ("Vertical line symmetry", "vertical.png"),
+            SymmetryClass::None => ("No symmetry", "none.png"),
+        };
+        let flags: Vec<(&str, &DynamicImage)> = flags
+            .iter()
+            .map(|(name, image)| (name.as_str(), image))
+            .collect();
+        let sheet = contact_sheet(&format!("{} ({})", title, flags.len()), &flags, layout);
+
+        let mut file =
+            AtomicFile::create(&dir.join(file_name), overwrite).map_err(already_exists_hint)?;
+        // the PNG encoder needs to seek, so it's encoded in memory first
+        let mut png = std::io::Cursor::new(Vec::new());
+        sheet.write_to(&mut png, ImageFormat::Png)?;
+        file.write_all(png.get_ref())?;
+        file.commit()?;
+    }
+    Ok(())
+}
+
+fn already_exists_hint(err: std::io::Error) -> String {
+    match err.kind() {
+        ErrorKind::AlreadyExists => format!("{}, pass --force to overwrite it", err),
+        _ => err.to_string(),
+    }
+}
+
+fn build_fetcher(options: &DownloadOptions) -> Result<FlagFetcher, Box<dyn Error>> {
+    // One HTTP client is shared by every download, see ClientConfig for what can be tuned
+    let mut client_config = ClientConfig::default();
+    if let Some(seconds) = options.connect_timeout {
+        client_config.connect_timeout = Duration::from_secs(seconds);
+    }
+    if let Some(seconds) = options.read_timeout {
+        client_config.read_timeout = Duration::from_secs(seconds);
+    }
+    if let Some(user_agent) = &options.user_agent {
+        client_config.user_agent = user_agent.clone();
+    }
+    client_config.proxy = options.proxy.clone();
+    client_config.root_ca = options.root_ca.clone();
+    let mut fetcher = FlagFetcher::new(client_config.build()?, options.rate);
+
+    if let Some(template) = &options.url_template {
+        fetcher = fetcher.with_url_template(template.clone());
+    }
+    if let Some(template) = &options.svg_url_template {
+        fetcher = fetcher.with_svg_url_template(template.clone());
+    }
+    if let Some(path) = &options.manifest {
+        fetcher = fetcher.with_manifest(Manifest::load(path)?, options.strict_manifest);
+    }
+    if let Some(dir) = options.cache() {
+        fetcher = fetcher.with_cache(dir.clone());
+    }
+    Ok(fetcher)
+}
+
+fn select_entries(selection: &Selection) -> Result<Vec<FlagEntry>, Box<dyn Error>> {
+    let datasets = selection
+        .datasets
+        .clone()
+        .unwrap_or_else(|| DEFAULT_DATASETS.to_vec());
+    let mut entries: Vec<FlagEntry> = sorted_countries(&datasets, selection.sort)
+        .into_iter()
+        .map(FlagEntry::from_country)
+        .collect();
+
+    if let Some(path) = &selection.dataset {
+        if selection.dataset_only {
+            entries.clear();
+        }
+        let custom = load_dataset(path, &entries)?;
+        entries.extend(custom);
+    }
+
+    let mut country_filter = CountryFilter::default();
+    if let Some(ids) = &selection.only {
+        country_filter.only = CountryFilter::parse_ids(ids);
+    }
+    if let Some(ids) = &selection.exclude {
+        country_filter.exclude = CountryFilter::parse_ids(ids);
+    }
+    if let Some(regions) = &selection.region {
+        country_filter.regions = CountryFilter::parse_regions(regions)?;
+    }
+    country_filter.status = match (selection.sovereign_only, selection.territories_only) {
+        (true, _) => Some(Status::Sovereign),
+        (false, true) => Some(Status::Territory),
+        (false, false) => None,
+    };
+    let mut entries = country_filter.apply(entries)?;
+
+    localize(&mut entries, selection.lang);
+    if selection.sort == countries::CountryOrder::Name {
+        // translated names sort differently, and user dataset entries need placing too
+        entries.sort_by_cached_key(|entry| (name_sort_key(&entry.name), entry.id.clone()));
+    }
+    Ok(entries)
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+fn class_title(class: SymmetryClass) -> &'static str {
+    match class {
+        SymmetryClass::Full => "Full symmetry",
+        SymmetryClass::Horizontal => "Horizontal line symmetry",
+        SymmetryClass::Vertical => "Vertical line symmetry",
+        SymmetryClass::None => "No symmetry",
+    }
+}
+
+fn summary_table(totals: &Totals) -> Table {
+    let mut table = Table::new();
+    table.add_row(row![
+        "🪩 Flags with full symmetry",
+        totals.count(SymmetryClass::Full)
+    ]);
     table.add_row(row![
         "↕️ Flags with horizontal line symmetry",
-        total_horizontal.0
+        totals.count(SymmetryClass::Horizontal)
     ]);
     table.add_row(row![
         "↔️ Flags with vertical line symmetry",
-        total_vertical.0
+        totals.count(SymmetryClass::Vertical)
     ]);
-    table.add_row(row!["❌ Flags with no symmetry", total_none.0]);
+    table.add_row(row![
+        "❌ Flags with no symmetry",
+        totals.count(SymmetryClass::None)
+    ]);
+    table
+}
 
-    table.printstd();
+async fn report_result_summary(totals: &Totals, options: &SummaryOptions, pause: Duration) {
+    println!("\n\n\n");
+    println!("In summary: ");
+    summary_table(totals).printstd();
 
     // delay for a sec
-    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    tokio::time::sleep(pause).await;
     println!("\n\n\n");
-    println!("Full symmetry: {}", total_full.1);
-    println!("Horizontal line symmetry: {}", total_horizontal.1);
-    println!("Vertical line symmetry: {}", total_vertical.1);
-    println!("No symmetry: {}", total_none.1);
+    for class in SymmetryClass::ALL {
+        println!("{}: {}", class_title(class), totals.list(class, options));
+    }
+}
+
+fn report_group_summaries(totals: &Totals) {
+    // flags from a user dataset have no registry metadata, they're grouped as "Custom"
+    let region_totals = totals
+        .by_region
+        .iter()
+        .map(|(region, counts)| match region {
+            Some(region) => (region.to_string(), *counts),
+            None => ("Custom".to_string(), *counts),
+        });
+    report_group_summary("By region", region_totals);
+    let status_totals = totals
+        .by_status
+        .iter()
+        .map(|(status, counts)| match status {
+            Some((status, true)) => (format!("{} (UN member)", status), *counts),
+            Some((status, false)) => (status.to_string(), *counts),
+            None => ("Custom".to_string(), *counts),
+        });
+    report_group_summary("By status", status_totals);
+}
+
+fn report_group_summary(title: &str, totals: impl Iterator<Item = (String, [i64; 4])>) {
+    // same categories as the main summary, broken down by a country attribute
+    let mut table = Table::new();
+
+    println!("\n");
+    println!("{}: ", title);
+    table.add_row(row![
+        "",
+        "🪩 Full",
+        "↕️ Horizontal",
+        "↔️ Vertical",
+        "❌ None"
+    ]);
+    for (group, [full, horizontal, vertical, none]) in totals {
+        table.add_row(row![group, full, horizontal, vertical, none]);
+    }
+
+    table.printstd();
+}
+
+fn report_download_stats(fetcher: &FlagFetcher) {
+    println!("Download retries: {}", fetcher.retry_count());
+
+    if let Some(manifest) = fetcher.manifest() {
+        println!(
+            "Flags changed since the last run: {}",
+            manifest.changes().len()
+        );
+        for change in manifest.changes() {
+            println!("  {}", change);
+        }
+    }
+}
+
+fn save_manifest(fetcher: &FlagFetcher) -> Result<(), Box<dyn Error>> {
+    if let Some(manifest) = fetcher.manifest() {
+        manifest.save()?;
+    }
+    Ok(())
+}
+
+// only raised with --strict-manifest: stop before the results or the manifest are written, so the
+// change isn't silently accepted and is reported again on the next run
+fn manifest_mismatch(entry: &FlagEntry, err: &FetchError) -> Box<dyn Error> {
+    format!(
+        "{} {}: {}, stopping without writing results or updating the manifest",
+        entry.emoji, entry.name, err
+    )
+    .into()
+}
+
+fn report_failures(action: &str, failures: &[String]) -> Result<(), Box<dyn Error>> {
+    // flags that failed don't stop the others, but still make the command fail
+    if failures.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "failed to {} {} flags:\n  {}",
+        action,
+        failures.len(),
+        failures.join("\n  ")
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn flag(code: &str, name: &str, emoji: &str) -> SummaryFlag {
+        SummaryFlag {
+            code: code.to_string(),
+            name: name.to_string(),
+            emoji: emoji.to_string(),
+        }
+    }
+
+    fn totals() -> Totals {
+        let mut totals = Totals::default();
+        for flag in [
+            flag("se", "Sweden", "🇸🇪"),
+            flag("ax", "Åland Islands", "🇦🇽"),
+            flag("", "Acme Corp", "🏳️"),
+            flag("ch", "Switzerland", "🇨🇭"),
+        ] {
+            totals.add(flag, None, SymmetryClass::None);
+        }
+        totals
+    }
+
+    #[test]
+    fn summary_lists_keep_the_results_order_by_default() {
+        let options = SummaryOptions {
+            summary_sort: None,
+            summary_emoji: false,
+        };
+
+        assert_eq!(
+            totals().list(SymmetryClass::None, &options),
+            "Sweden (se); Åland Islands (ax); Acme Corp; Switzerland (ch)"
+        );
+        assert_eq!(totals().list(SymmetryClass::Full, &options), "");
+    }
+
+    #[test]
+    fn summary_lists_sort_by_folded_name() {
+        let options = SummaryOptions {
+            summary_sort: Some(CountryOrder::Name),
+            summary_emoji: true,
+        };
+
+        assert_eq!(
+            totals().list(SymmetryClass::None, &options),
+            "🏳️ Acme Corp; 🇦🇽 Åland Islands (ax); 🇸🇪 Sweden (se); 🇨🇭 Switzerland (ch)"
+        );
+    }
+
+    #[test]
+    fn summary_lists_sort_by_code() {
+        let options = SummaryOptions {
+            summary_sort: Some(CountryOrder::Code),
+            summary_emoji: false,
+        };
+
+        assert_eq!(
+            totals().list(SymmetryClass::None, &options),
+            "Acme Corp; Åland Islands (ax); Switzerland (ch); Sweden (se)"
+        );
+    }
+
+    #[test]
+    fn markdown_summary_lists_flags_per_class_and_where_they_came_from() {
+        let options = SummaryOptions {
+            summary_sort: None,
+            summary_emoji: true,
+        };
+        let summary = markdown_summary(&totals(), &options, "results.csv", None);
+        let lines: Vec<&str> = summary.lines().collect();
+
+        assert_eq!(lines[0], "```");
+        assert!(lines.contains(&"**Full symmetry flags (0):** "));
+        assert!(lines.contains(
+            &"**No symmetry flags (4):** 🇸🇪 Sweden (se); 🇦🇽 Åland Islands (ax); 🏳️ Acme Corp; 🇨🇭 Switzerland (ch)"
+        ));
+        assert!(lines.contains(&"\\*flags that do not have an associated emoji are shown as 🏳️"));
+        assert_eq!(
+            lines.last(),
+            Some(&"<sub>Generated from `results.csv` (4 flags).</sub>")
+        );
+    }
+
+    #[test]
+    fn summary_section_needs_both_markers_in_order() {
+        let start_only = format!("# Flags\n{}\n", SUMMARY_START);
+        let end_first = format!("{}\nold\n{}\n", SUMMARY_END, SUMMARY_START);
+
+        assert_eq!(replace_summary_section("# Flags\n", "new"), None);
+        assert_eq!(replace_summary_section(&start_only, "new"), None);
+        assert_eq!(replace_summary_section(&end_first, "new"), None);
+    }
+
+    #[test]
+    fn summary_section_is_replaced_between_the_markers() {
+        let readme = format!(
+            "# Flags\n\n{}\nold table\n{}\n\n## Usage\n",
+            SUMMARY_START, SUMMARY_END
+        );
+
+        let updated = replace_summary_section(&readme, "new table").unwrap();
+
+        assert_eq!(
+            updated,
+            format!(
+                "# Flags\n\n{}\nnew table\n{}\n\n## Usage\n",
+                SUMMARY_START, SUMMARY_END
+            )
+        );
+        // running it again with the same summary changes nothing
+        assert_eq!(
+            replace_summary_section(&updated, "new table").unwrap(),
+            updated
+        );
+    }
+
+    fn png(img: RgbaImage) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut bytes, ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    // red over white, like Monaco
+    fn bicolour(width: u32, height: u32) -> Vec<u8> {
+        png(RgbaImage::from_fn(width, height, |_, y| {
+            if y < height / 2 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        }))
+    }
+
+    #[test]
+    fn stdin_check_exits_0_with_the_axis() {
+        let (output, code) = check_image_bytes(&bicolour(30, 20), Axis::Vertical);
+
+        assert_eq!(code, 0);
+        assert_eq!(
+            output,
+            serde_json::json!({
+                "source": "-",
+                "width": 30,
+                "height": 20,
+                "horizontal": false,
+                "vertical": true,
+                "rotational": false,
+                "class": "Vert",
+                "axis": "vertical",
+                "symmetric": true,
+            })
+        );
+    }
+
+    #[test]
+    fn stdin_check_exits_1_without_the_axis() {
+        let (output, code) = check_image_bytes(&bicolour(30, 20), Axis::Rotational);
+
+        assert_eq!(code, 1);
+        assert_eq!(output["symmetric"], false);
+    }
+
+    #[test]
+    fn stdin_check_exits_2_with_a_json_error() {
+        let (output, code) = check_image_bytes(b"<html>not found</html>", Axis::Any);
+
+        assert_eq!(code, 2);
+        assert_eq!(output["source"], "-");
+        assert!(output["error"].is_string());
+    }
+
+    #[test]
+    fn stdin_check_handles_very_wide_images() {
+        let (output, code) = check_image_bytes(&bicolour(40_000, 8), Axis::Vertical);
+
+        assert_eq!(code, 0);
+        assert_eq!(output["width"], 40_000);
+    }
 }
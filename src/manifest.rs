@@ -0,0 +1,141 @@
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::atomic_file::AtomicFile;
+
+const MANIFEST_HEADERS: [&str; 4] = ["code", "size", "sha256", "fetched_at"];
+
+#[derive(Debug, Clone)]
+pub struct ManifestEntry {
+    pub sha256: String,
+    pub fetched_at: String,
+}
+
+/// A flag whose downloaded image no longer matches the hash in the manifest.
+#[derive(Debug, Clone)]
+pub struct FlagChange {
+    pub code: String,
+    pub size: String,
+    pub old_sha256: String,
+    pub new_sha256: String,
+    pub old_fetched_at: String,
+}
+
+impl fmt::Display for FlagChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}) changed since {}: {} -> {}",
+            self.code, self.size, self.old_fetched_at, self.old_sha256, self.new_sha256
+        )
+    }
+}
+
+/// Record of the SHA-256 of every downloaded flag, used to spot flags that changed upstream between runs.
+/// Stored as a CSV with one row per (code, size).
+///
+/// ```
+/// use flag_symmetry::manifest::{sha256_hex, Manifest};
+///
+/// let dir = tempfile::tempdir()?;
+/// let path = dir.path().join("manifest.csv");
+/// let mut manifest = Manifest::load(&path)?;
+/// assert!(manifest.record("jp", "h80", sha256_hex(b"red disc")).is_none());
+/// manifest.save()?;
+///
+/// let mut manifest = Manifest::load(&path)?;
+/// let change = manifest.record("jp", "h80", sha256_hex(b"redrawn disc")).unwrap();
+/// assert_eq!(change.old_sha256, sha256_hex(b"red disc"));
+/// assert_eq!(manifest.changes().len(), 1);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Manifest {
+    path: PathBuf,
+    entries: BTreeMap<(String, String), ManifestEntry>,
+    changes: Vec<FlagChange>,
+}
+
+impl Manifest {
+    /// Loads the manifest at `path`, or starts an empty one if the file doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Manifest, csv::Error> {
+        let mut entries = BTreeMap::new();
+
+        if path.exists() {
+            let mut reader = csv::Reader::from_path(path)?;
+            for record in reader.records() {
+                let record = record?;
+                entries.insert(
+                    (record[0].to_string(), record[1].to_string()),
+                    ManifestEntry {
+                        sha256: record[2].to_string(),
+                        fetched_at: record[3].to_string(),
+                    },
+                );
+            }
+        }
+
+        Ok(Manifest {
+            path: path.to_path_buf(),
+            entries,
+            changes: Vec::new(),
+        })
+    }
+
+    /// The recorded hash of a flag at one size, e.g. ("jp", "h80").
+    pub fn lookup(&self, code: &str, size: &str) -> Option<&ManifestEntry> {
+        self.entries.get(&(code.to_string(), size.to_string()))
+    }
+
+    /// Stores the hash of a fresh download, returning the change if it differs from the previous one.
+    ///
+    /// A flag whose hash hasn't changed keeps the entry, and so the `fetched_at`, it already had.
+    pub fn record(&mut self, code: &str, size: &str, sha256: String) -> Option<FlagChange> {
+        let key = (code.to_string(), size.to_string());
+        if self
+            .entries
+            .get(&key)
+            .is_some_and(|entry| entry.sha256 == sha256)
+        {
+            return None;
+        }
+        let entry = ManifestEntry {
+            sha256,
+            fetched_at: httpdate::fmt_http_date(SystemTime::now()),
+        };
+        let previous = self.entries.insert(key, entry.clone())?;
+        let change = FlagChange {
+            code: code.to_string(),
+            size: size.to_string(),
+            old_sha256: previous.sha256,
+            new_sha256: entry.sha256,
+            old_fetched_at: previous.fetched_at,
+        };
+        self.changes.push(change.clone());
+        Some(change)
+    }
+
+    /// Flags that changed since the manifest was loaded.
+    pub fn changes(&self) -> &[FlagChange] {
+        &self.changes
+    }
+
+    /// Writes the manifest back to the path it was loaded from, atomically.
+    pub fn save(&self) -> Result<(), csv::Error> {
+        let mut writer = csv::Writer::from_writer(AtomicFile::create(&self.path, true)?);
+        writer.write_record(MANIFEST_HEADERS)?;
+        for ((code, size), entry) in &self.entries {
+            writer.write_record([code, size, &entry.sha256, &entry.fetched_at])?;
+        }
+        let file = writer.into_inner().map_err(|err| err.into_error())?;
+        file.commit()?;
+        Ok(())
+    }
+}
+
+/// Lowercase hex SHA-256, as stored in the manifest.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
@@ -0,0 +1,67 @@
+use image::{DynamicImage, GenericImageView};
+use serde::Serialize;
+use std::collections::HashMap;
+
+const MIN_SHARE: f32 = 0.01; // colors covering less of the flag than this are left out
+
+/// One of a flag's main colors.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PaletteColor {
+    pub hex: String, // "#rrggbb"
+    pub share: f32,  // fraction of the opaque pixels
+}
+
+/// The flag's main colors, most common first, ignoring transparent pixels.
+pub fn palette(img: &DynamicImage, max_colors: usize) -> Vec<PaletteColor> {
+    let mut counts: HashMap<[u8; 3], usize> = HashMap::new();
+    let mut total = 0;
+    for (_, _, pixel) in img.pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a < 128 {
+            continue;
+        }
+        total += 1;
+        *counts.entry([r, g, b].map(quantize)).or_default() += 1;
+    }
+    if total == 0 {
+        return Vec::new();
+    }
+
+    let mut colors: Vec<([u8; 3], usize)> = counts.into_iter().collect();
+    // ties are broken by color so the order is stable between runs
+    colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    colors
+        .into_iter()
+        .map(|([r, g, b], count)| PaletteColor {
+            hex: format!("#{:02x}{:02x}{:02x}", r, g, b),
+            share: count as f32 / total as f32,
+        })
+        .filter(|color| color.share >= MIN_SHARE)
+        .take(max_colors)
+        .collect()
+}
+
+fn quantize(channel: u8) -> u8 {
+    // 16 levels per channel (0x00, 0x11, ... 0xff) so anti-aliased edges fold into their stripe
+    let high = channel & 0xf0;
+    high | (high >> 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn most_common_color_first() {
+        let flag = RgbaImage::from_fn(4, 2, |x, _| match x {
+            0 => Rgba([255, 0, 0, 255]),
+            _ => Rgba([255, 255, 255, 255]),
+        });
+
+        let colors = palette(&DynamicImage::ImageRgba8(flag), 5);
+
+        assert_eq!(colors[0].hex, "#ffffff");
+        assert_eq!(colors[1].share, 0.25);
+    }
+}
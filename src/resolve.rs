@@ -0,0 +1,182 @@
+use std::fmt;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::consts::countries::{self, Country, Dataset};
+use crate::flags::FALLBACK_EMOJI;
+
+const SUGGESTION_THRESHOLD: f64 = 0.8; // minimum Jaro-Winkler similarity for a "did you mean"
+const MAX_SUGGESTIONS: usize = 3;
+
+const REGIONAL_INDICATOR_A: u32 = 0x1F1E6;
+const TAG_LATIN_A: u32 = 0xE0061; // tag characters spell out subdivision codes in 🏴 sequences
+
+#[derive(Debug)]
+pub struct ResolveError {
+    pub input: String,
+    pub suggestions: Vec<&'static str>,
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown flag '{}'", self.input)?;
+        if !self.suggestions.is_empty() {
+            write!(f, ", did you mean {}?", self.suggestions.join(" or "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Finds a registry entry from whatever an analyst typed: alpha-2 ("jp"), alpha-3 ("JPN"),
+/// numeric ("392"), English name ("japan", "Cote d'Ivoire") or emoji (🇯🇵).
+///
+/// ```
+/// use flag_symmetry::resolve;
+///
+/// assert_eq!(resolve("JPN").unwrap().code, "jp");
+/// assert_eq!(resolve("🇯🇵").unwrap().code, "jp");
+/// assert!(resolve("Japn").unwrap_err().suggestions.contains(&"Japan"));
+/// ```
+pub fn resolve(input: &str) -> Result<&'static Country, ResolveError> {
+    let trimmed = input.trim();
+    let code = trimmed.to_lowercase();
+
+    let found = countries::lookup(&code)
+        .or_else(|| from_emoji(trimmed))
+        .or_else(|| all_entries().find(|c| c.alpha3.is_some_and(|a| a.eq_ignore_ascii_case(&code))))
+        .or_else(|| {
+            let numeric = trimmed.parse::<u16>().ok()?;
+            all_entries().find(|c| c.numeric == Some(numeric))
+        })
+        .or_else(|| {
+            let name = normalize(trimmed);
+            all_entries().find(|c| name_variants(c.name).contains(&name))
+        });
+
+    found.ok_or_else(|| ResolveError {
+        input: input.to_string(),
+        suggestions: suggest(trimmed),
+    })
+}
+
+fn all_entries() -> impl Iterator<Item = &'static Country> {
+    Dataset::ALL.iter().flat_map(|dataset| dataset.entries())
+}
+
+fn from_emoji(input: &str) -> Option<&'static Country> {
+    // every flag without a dedicated emoji shares the fallback, so it can't identify one
+    if input == FALLBACK_EMOJI {
+        return None;
+    }
+    if let Some(country) = all_entries().find(|c| c.emoji == input) {
+        return Some(country);
+    }
+
+    // 🇯🇵 is two regional indicator letters, 🏴󠁧󠁢󠁳󠁣󠁴󠁿 is a black flag followed by tag letters
+    let letters: String = input
+        .chars()
+        .filter_map(|c| {
+            let c = c as u32;
+            let offset = match c {
+                0x1F1E6..=0x1F1FF => c - REGIONAL_INDICATOR_A,
+                0xE0061..=0xE007A => c - TAG_LATIN_A,
+                _ => return None,
+            };
+            char::from_u32('a' as u32 + offset)
+        })
+        .collect();
+
+    match letters.len() {
+        2 => countries::lookup(&letters),
+        5 => countries::lookup(&format!("{}-{}", &letters[..2], &letters[2..])),
+        _ => None,
+    }
+}
+
+/// Lowercase, strip diacritics and collapse punctuation so "Côte d’Ivoire" matches "cote d ivoire".
+fn normalize(s: &str) -> String {
+    let stripped: String = s
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn name_variants(name: &str) -> Vec<String> {
+    // "Côte d'Ivoire (Ivory Coast)" can be found by either name
+    let mut variants = vec![normalize(name)];
+    if let Some((before, after)) = name.split_once('(') {
+        variants.push(normalize(before));
+        variants.push(normalize(after.trim_end_matches(')')));
+    }
+    variants
+}
+
+fn suggest(input: &str) -> Vec<&'static str> {
+    let input = normalize(input);
+    let mut scored: Vec<(f64, &'static str)> = all_entries()
+        .map(|country| {
+            let score = name_variants(country.name)
+                .iter()
+                .map(|variant| strsim::jaro_winkler(&input, variant))
+                .fold(0.0, f64::max);
+            (score, country.name)
+        })
+        .filter(|(score, _)| *score >= SUGGESTION_THRESHOLD)
+        .collect();
+
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_codes_and_emoji() {
+        assert_eq!(resolve("DE").unwrap().code, "de");
+        assert_eq!(resolve("deu").unwrap().code, "de");
+        assert_eq!(resolve("276").unwrap().code, "de");
+        assert_eq!(resolve("🇩🇪").unwrap().code, "de");
+        assert_eq!(resolve("🏴󠁧󠁢󠁥󠁮󠁧󠁿").unwrap().code, "gb-eng");
+    }
+
+    #[test]
+    fn names_match_without_diacritics() {
+        assert_eq!(resolve("curacao").unwrap().code, "cw");
+        assert_eq!(resolve("REUNION").unwrap().code, "re");
+        assert_eq!(resolve("Cote d’Ivoire").unwrap().code, "ci");
+        assert_eq!(resolve("ivory coast").unwrap().code, "ci");
+    }
+
+    #[test]
+    fn non_ascii_letters_are_lowercased() {
+        assert_eq!(resolve("ÅLAND ISLANDS").unwrap().code, "ax");
+        assert_eq!(normalize("ØRESUND Ænes"), "øresund ænes");
+        assert_eq!(normalize("ΕΛΛΆΔΑ"), normalize("ελλάδα"));
+    }
+
+    #[test]
+    fn the_fallback_emoji_is_not_a_flag() {
+        let err = resolve(FALLBACK_EMOJI).unwrap_err();
+
+        assert_eq!(err.input, FALLBACK_EMOJI);
+    }
+
+    #[test]
+    fn suggests_close_names() {
+        let err = resolve("Germny").unwrap_err();
+
+        assert_eq!(err.suggestions.first(), Some(&"Germany"));
+        assert!(resolve("xyzzy").unwrap_err().suggestions.is_empty());
+    }
+}
@@ -0,0 +1,648 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use crate::analysis::{FlagResult, SymmetryClass};
+use crate::consts::countries::{Region, Status};
+use crate::flags::FlagEntry;
+use crate::palette::PaletteColor;
+use crate::resolve::resolve;
+use crate::symmetry::{Symmetry, SymmetryScores};
+
+/// One row of a results file such as results.csv.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultRecord {
+    #[serde(rename = "Flag")]
+    pub emoji: String,
+    #[serde(rename = "Country name")]
+    pub name: String,
+    #[serde(rename = "Line of symmetry")]
+    pub class: SymmetryClass,
+    // results files written before the code column existed are still readable
+    #[serde(rename = "Code", default)]
+    pub code: String,
+}
+
+impl ResultRecord {
+    /// What rows are matched on when comparing two results files.
+    pub fn key(&self) -> &str {
+        if self.code.is_empty() {
+            &self.name
+        } else {
+            &self.code
+        }
+    }
+}
+
+impl From<&FlagResult> for ResultRecord {
+    fn from(result: &FlagResult) -> Self {
+        ResultRecord {
+            emoji: result.entry.emoji.clone(),
+            name: result.entry.name.clone(),
+            class: result.class(),
+            code: result.entry.id.clone(),
+        }
+    }
+}
+
+/// A results file read back in. Only JSON and NDJSON files carry `metadata`,
+/// and flags that failed to analyse are left out of `records`.
+pub struct ResultsFile {
+    pub records: Vec<ResultRecord>,
+    pub metadata: Option<RunMetadata>,
+}
+
+// the parts of a JSON flag report that a ResultRecord needs
+#[derive(Deserialize)]
+struct StoredFlag {
+    code: String,
+    name: String,
+    emoji: String,
+    class: Option<SymmetryClass>,
+}
+
+#[derive(Deserialize)]
+struct StoredDocument {
+    metadata: RunMetadata,
+    flags: Vec<StoredFlag>,
+}
+
+#[derive(Deserialize)]
+struct StoredMetadataLine {
+    metadata: RunMetadata,
+}
+
+/// Reads a results file written by `run`, the format is picked from the extension (CSV unless
+/// it's .json or .ndjson).
+///
+/// ```
+/// use flag_symmetry::read_results;
+/// use flag_symmetry::analysis::SymmetryClass;
+///
+/// let dir = tempfile::tempdir()?;
+/// let path = dir.path().join("results.csv");
+/// std::fs::write(&path, "Flag,Country name,Line of symmetry,Code\n🇯🇵,Japan,Full,jp\n")?;
+///
+/// let results = read_results(&path)?;
+/// assert_eq!(results.records[0].code, "jp");
+/// assert_eq!(results.records[0].class, SymmetryClass::Full);
+/// assert!(results.metadata.is_none()); // only JSON and NDJSON files describe the run
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_results(path: &Path) -> Result<ResultsFile, ResultsError> {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let (flags, metadata) = match extension.parse::<OutputFormat>() {
+        Ok(OutputFormat::Json) => {
+            let document: StoredDocument = serde_json::from_slice(&std::fs::read(path)?)?;
+            (document.flags, Some(document.metadata))
+        }
+        Ok(OutputFormat::Ndjson) => {
+            let text = std::fs::read_to_string(path)?;
+            let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+            let metadata = match lines.next() {
+                Some(line) => Some(serde_json::from_str::<StoredMetadataLine>(line)?.metadata),
+                None => None,
+            };
+            let flags = lines
+                .map(serde_json::from_str)
+                .collect::<Result<Vec<StoredFlag>, _>>()?;
+            (flags, metadata)
+        }
+        _ => {
+            return Ok(ResultsFile {
+                records: read_csv_results(path)?,
+                metadata: None,
+            })
+        }
+    };
+
+    let records = flags
+        .into_iter()
+        .filter_map(|flag| {
+            Some(ResultRecord {
+                class: flag.class?,
+                emoji: flag.emoji,
+                name: flag.name,
+                code: flag.code,
+            })
+        })
+        .collect();
+    Ok(ResultsFile { records, metadata })
+}
+
+fn read_csv_results(path: &Path) -> Result<Vec<ResultRecord>, csv::Error> {
+    let mut records: Vec<ResultRecord> = csv::Reader::from_path(path)?
+        .deserialize()
+        .collect::<Result<_, _>>()?;
+
+    // rows are matched on the code column, so names written with --lang never need resolving.
+    // Older files only have names, recover the code where the name is an English registry one
+    for record in records.iter_mut().filter(|record| record.code.is_empty()) {
+        if let Ok(country) = resolve(&record.name) {
+            record.code = country.code.to_string();
+        }
+    }
+    Ok(records)
+}
+
+/// A flag whose class differs between two results files. `None` means it's missing from that file.
+#[derive(Debug)]
+pub struct ResultChange {
+    pub record: ResultRecord, // the newer row if there is one
+    pub old: Option<SymmetryClass>,
+    pub new: Option<SymmetryClass>,
+}
+
+/// Flags that were added, removed or changed class between `old` and `new`, in `new`'s order
+/// followed by the removed ones.
+pub fn diff_results(old: &[ResultRecord], new: &[ResultRecord]) -> Vec<ResultChange> {
+    let old_by_key: BTreeMap<&str, &ResultRecord> =
+        old.iter().map(|record| (record.key(), record)).collect();
+    let new_by_key: BTreeMap<&str, &ResultRecord> =
+        new.iter().map(|record| (record.key(), record)).collect();
+
+    let changed = new.iter().filter_map(|record| {
+        let old_class = old_by_key.get(record.key()).map(|old| old.class);
+        (old_class != Some(record.class)).then(|| ResultChange {
+            record: record.clone(),
+            old: old_class,
+            new: Some(record.class),
+        })
+    });
+    let removed = old
+        .iter()
+        .filter(|record| !new_by_key.contains_key(record.key()))
+        .map(|record| ResultChange {
+            record: record.clone(),
+            old: Some(record.class),
+            new: None,
+        });
+
+    changed.chain(removed).collect()
+}
+
+// bumped whenever the JSON layout changes, see schema/results.v1.schema.json
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            _ => Err(format!(
+                "unknown format '{}', expected csv, json or ndjson",
+                s
+            )),
+        }
+    }
+}
+
+/// Describes the run that produced a JSON or NDJSON results file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunMetadata {
+    pub tool: String,
+    pub version: String,
+    pub schema_version: u32,
+    pub generated_at: String, // HTTP date, like the manifest's timestamps
+    pub config: serde_json::Value,
+}
+
+impl RunMetadata {
+    pub fn new(config: impl Serialize) -> Result<RunMetadata, serde_json::Error> {
+        Ok(RunMetadata {
+            tool: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: SCHEMA_VERSION,
+            generated_at: httpdate::fmt_http_date(SystemTime::now()),
+            config: serde_json::to_value(config)?,
+        })
+    }
+}
+
+/// Everything known about one flag, as written to JSON and NDJSON results.
+/// Analysis fields are `None` when the flag couldn't be fetched or decoded, see `error`.
+#[derive(Debug, Serialize)]
+pub struct FlagReport {
+    pub code: String,
+    pub name: String,
+    pub emoji: String,
+    pub region: Option<Region>,
+    pub status: Option<Status>,
+    pub un_member: Option<bool>,
+    pub source: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub horizontal: Option<bool>,
+    pub vertical: Option<bool>,
+    pub rotational: Option<bool>,
+    pub class: Option<SymmetryClass>,
+    pub scores: Option<SymmetryScores>,
+    pub palette: Vec<PaletteColor>,
+    pub vector: Option<Symmetry>,
+    pub error: Option<String>,
+}
+
+impl FlagReport {
+    pub fn from_result(result: &FlagResult) -> FlagReport {
+        FlagReport {
+            width: Some(result.width),
+            height: Some(result.height),
+            horizontal: Some(result.symmetry.horizontal),
+            vertical: Some(result.symmetry.vertical),
+            rotational: Some(result.symmetry.rotational),
+            class: Some(result.class()),
+            scores: Some(result.scores),
+            palette: result.palette.clone(),
+            vector: result.vector_symmetry,
+            ..FlagReport::new(&result.entry, result.source.clone())
+        }
+    }
+
+    pub fn from_error(entry: &FlagEntry, source: String, error: &dyn fmt::Display) -> FlagReport {
+        FlagReport {
+            error: Some(error.to_string()),
+            ..FlagReport::new(entry, source)
+        }
+    }
+
+    fn new(entry: &FlagEntry, source: String) -> FlagReport {
+        FlagReport {
+            code: entry.id.clone(),
+            name: entry.name.clone(),
+            emoji: entry.emoji.clone(),
+            region: entry.country.map(|country| country.region),
+            status: entry.country.map(|country| country.status),
+            un_member: entry.country.map(|country| country.un_member),
+            source,
+            width: None,
+            height: None,
+            horizontal: None,
+            vertical: None,
+            rotational: None,
+            class: None,
+            scores: None,
+            palette: Vec::new(),
+            vector: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ResultsError {
+    Io(std::io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ResultsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResultsError::Io(err) => write!(f, "results file: {}", err),
+            ResultsError::Csv(err) => write!(f, "results file: invalid CSV: {}", err),
+            ResultsError::Json(err) => write!(f, "results file: invalid JSON: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ResultsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ResultsError::Io(err) => Some(err),
+            ResultsError::Csv(err) => Some(err),
+            ResultsError::Json(err) => Some(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for ResultsError {
+    fn from(err: std::io::Error) -> Self {
+        ResultsError::Io(err)
+    }
+}
+
+impl From<csv::Error> for ResultsError {
+    fn from(err: csv::Error) -> Self {
+        ResultsError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for ResultsError {
+    fn from(err: serde_json::Error) -> Self {
+        ResultsError::Json(err)
+    }
+}
+
+// layout of a JSON results file
+#[derive(Serialize)]
+struct ResultsDocument<'a> {
+    metadata: &'a RunMetadata,
+    flags: &'a [FlagReport],
+}
+
+// first line of an NDJSON results file
+#[derive(Serialize)]
+struct MetadataLine<'a> {
+    metadata: &'a RunMetadata,
+}
+
+/// Writes flag reports in any `OutputFormat`.
+/// CSV keeps the results.csv columns and leaves out flags that failed, JSON is written in one go by `finish`.
+///
+/// ```
+/// use flag_symmetry::results::{OutputFormat, RunMetadata};
+/// use flag_symmetry::{lookup, FlagEntry, FlagReport, ResultsWriter};
+///
+/// let france = FlagEntry::from_country(lookup("fr").unwrap());
+/// let mut writer = ResultsWriter::new(Vec::new(), OutputFormat::Ndjson, RunMetadata::new(())?)?;
+/// writer.write(FlagReport::from_error(&france, "fr.png".into(), &"timed out"))?;
+/// let ndjson = String::from_utf8(writer.finish()?)?;
+///
+/// // a metadata line, then one line per flag
+/// assert_eq!(ndjson.lines().count(), 2);
+/// assert!(ndjson.contains("\"error\":\"timed out\""));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub enum ResultsWriter<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Json {
+        writer: W,
+        metadata: RunMetadata,
+        flags: Vec<FlagReport>,
+    },
+    Ndjson(W),
+}
+
+impl<W: Write> ResultsWriter<W> {
+    pub fn new(
+        mut writer: W,
+        format: OutputFormat,
+        metadata: RunMetadata,
+    ) -> Result<ResultsWriter<W>, ResultsError> {
+        Ok(match format {
+            OutputFormat::Csv => ResultsWriter::Csv(Box::new(csv::Writer::from_writer(writer))),
+            OutputFormat::Json => ResultsWriter::Json {
+                writer,
+                metadata,
+                flags: Vec::new(),
+            },
+            OutputFormat::Ndjson => {
+                // the first line describes the run, every following line is one flag
+                serde_json::to_writer(
+                    &mut writer,
+                    &MetadataLine {
+                        metadata: &metadata,
+                    },
+                )?;
+                writeln!(writer)?;
+                ResultsWriter::Ndjson(writer)
+            }
+        })
+    }
+
+    pub fn write(&mut self, report: FlagReport) -> Result<(), ResultsError> {
+        match self {
+            ResultsWriter::Csv(writer) => {
+                if let Some(class) = report.class {
+                    writer.serialize(ResultRecord {
+                        emoji: report.emoji,
+                        name: report.name,
+                        class,
+                        code: report.code,
+                    })?;
+                }
+            }
+            ResultsWriter::Json { flags, .. } => flags.push(report),
+            ResultsWriter::Ndjson(writer) => {
+                serde_json::to_writer(&mut *writer, &report)?;
+                writeln!(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes anything still buffered and hands back the underlying writer.
+    pub fn finish(self) -> Result<W, ResultsError> {
+        let mut writer = match self {
+            ResultsWriter::Csv(writer) => writer.into_inner().map_err(|err| err.into_error())?,
+            ResultsWriter::Json {
+                mut writer,
+                metadata,
+                flags,
+            } => {
+                let document = ResultsDocument {
+                    metadata: &metadata,
+                    flags: &flags,
+                };
+                serde_json::to_writer_pretty(&mut writer, &document)?;
+                writeln!(writer)?;
+                writer
+            }
+            ResultsWriter::Ndjson(writer) => writer,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::countries::lookup;
+    use serde_json::Value;
+    use tempfile::tempdir;
+
+    const SCHEMA: &str = include_str!("../schema/results.v1.schema.json");
+
+    fn sorted_keys(value: &Value) -> Vec<String> {
+        let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    fn required(schema: &Value, definition: &str) -> Vec<String> {
+        let mut keys: Vec<String> = schema["$defs"][definition]["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|key| key.as_str().unwrap().to_string())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn flag_reports_match_the_schema() {
+        let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+        let entry = FlagEntry::from_country(lookup("fr").unwrap());
+        let report = FlagReport::from_error(
+            &entry,
+            "https://flagcdn.com/h80/fr.png".into(),
+            &"timed out",
+        );
+        let report = serde_json::to_value(report).unwrap();
+
+        assert_eq!(sorted_keys(&report), required(&schema, "flag"));
+        for field in ["region", "status"] {
+            let allowed = schema["$defs"]["flag"]["properties"][field]["enum"]
+                .as_array()
+                .unwrap();
+            assert!(
+                allowed.contains(&report[field]),
+                "{} = {}",
+                field,
+                report[field]
+            );
+        }
+    }
+
+    #[test]
+    fn metadata_matches_the_schema() {
+        let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+        let metadata = serde_json::to_value(RunMetadata::new(()).unwrap()).unwrap();
+
+        assert_eq!(sorted_keys(&metadata), required(&schema, "metadata"));
+        assert_eq!(
+            schema["$defs"]["metadata"]["properties"]["schema_version"]["const"],
+            SCHEMA_VERSION
+        );
+    }
+
+    #[test]
+    fn ndjson_results_read_back_without_failed_flags() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("results.ndjson");
+        let entry = FlagEntry::from_country(lookup("fr").unwrap());
+        let mut writer = ResultsWriter::new(
+            Vec::new(),
+            OutputFormat::Ndjson,
+            RunMetadata::new(()).unwrap(),
+        )
+        .unwrap();
+        writer
+            .write(FlagReport::from_error(
+                &entry,
+                "fr.png".into(),
+                &"timed out",
+            ))
+            .unwrap();
+        std::fs::write(&path, writer.finish().unwrap()).unwrap();
+
+        let results = read_results(&path).unwrap();
+
+        assert!(results.records.is_empty());
+        assert_eq!(results.metadata.unwrap().schema_version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn csv_results_resolve_by_code_then_name() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("results.csv");
+        // written with --lang ja, and by a version without the code column
+        std::fs::write(
+            &path,
+            "Flag,Country name,Line of symmetry,Code\n🇯🇵,日本,Full,jp\n🇫🇷,France,Horz,\n🏳️,Acme Corp,None,\n",
+        )
+        .unwrap();
+
+        let records = read_results(&path).unwrap().records;
+
+        let keys: Vec<(&str, &str)> = records
+            .iter()
+            .map(|record| (record.name.as_str(), record.key()))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![("日本", "jp"), ("France", "fr"), ("Acme Corp", "Acme Corp")]
+        );
+    }
+
+    fn record(name: &str, code: &str, class: SymmetryClass) -> ResultRecord {
+        ResultRecord {
+            emoji: String::new(),
+            name: name.to_string(),
+            class,
+            code: code.to_string(),
+        }
+    }
+
+    fn summary(
+        changes: &[ResultChange],
+    ) -> Vec<(&str, Option<SymmetryClass>, Option<SymmetryClass>)> {
+        changes
+            .iter()
+            .map(|change| (change.record.key(), change.old, change.new))
+            .collect()
+    }
+
+    #[test]
+    fn diff_follows_the_new_order_then_lists_removed_flags() {
+        use SymmetryClass::{Full, Horizontal, Vertical};
+        let old = [
+            record("Japan", "jp", Full),
+            record("France", "fr", Vertical),
+            record("Acme Corp", "", SymmetryClass::None),
+            record("Greenland", "gl", Horizontal),
+        ];
+        let new = [
+            record("Italy", "it", Vertical),
+            record("France", "fr", Vertical),
+            record("Japon", "jp", Horizontal), // renamed with --lang, matched on the code
+            record("Acme Corp", "", Full),
+        ];
+
+        assert_eq!(
+            summary(&diff_results(&old, &new)),
+            [
+                ("it", None, Some(Vertical)),
+                ("jp", Some(Full), Some(Horizontal)),
+                ("Acme Corp", Some(SymmetryClass::None), Some(Full)),
+                ("gl", Some(Horizontal), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_of_identical_results_is_empty() {
+        let records = [
+            record("Japan", "jp", SymmetryClass::Full),
+            record("Acme Corp", "", SymmetryClass::None),
+        ];
+        assert!(diff_results(&records, &records).is_empty());
+    }
+
+    #[test]
+    fn diff_keeps_the_newer_row() {
+        let old = [record("Japan", "jp", SymmetryClass::Full)];
+        let new = [record("日本", "jp", SymmetryClass::Vertical)];
+
+        let changes = diff_results(&old, &new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].record.name, "日本");
+    }
+}
@@ -1,24 +1,131 @@
-use image::{DynamicImage, GenericImageView, Rgba};
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use serde::Serialize;
+use std::str::FromStr;
 
 const FUZZY_WINDOW_DIM: usize = 3; // size of the one axis of window to use for fuzzy comparison
 const FUZZY_WINDOW_SIZE: usize = usize::pow(FUZZY_WINDOW_DIM, 2);
 const COLOR_TOLERANCE: f32 = 0.1; // percentage tolerance for color comparison
 
-#[derive(Debug)]
+/// Which symmetries a flag has. "horizontal" is a top/bottom mirror (think "D"),
+/// "vertical" a left/right mirror (think "A") and "rotational" a half turn.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub struct Symmetry {
     pub horizontal: bool,
     pub vertical: bool,
+    pub rotational: bool,
 }
 
+/// The symmetry a caller cares about, e.g. to decide a pass/fail exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+    Rotational,
+    Full, // both lines
+    Any,
+}
+
+impl FromStr for Axis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "horizontal" => Ok(Axis::Horizontal),
+            "vertical" => Ok(Axis::Vertical),
+            "rotational" => Ok(Axis::Rotational),
+            "full" => Ok(Axis::Full),
+            "any" => Ok(Axis::Any),
+            _ => Err(format!(
+                "unknown axis '{}', expected horizontal, vertical, rotational, full or any",
+                s
+            )),
+        }
+    }
+}
+
+impl Symmetry {
+    /// ```
+    /// use flag_symmetry::symmetry::{Axis, Symmetry};
+    ///
+    /// let japan = Symmetry { horizontal: true, vertical: true, rotational: true };
+    /// assert!(japan.has(Axis::Full));
+    /// let france = Symmetry { horizontal: true, vertical: false, rotational: false };
+    /// assert!(france.has(Axis::Any) && !france.has(Axis::Rotational));
+    /// ```
+    pub fn has(&self, axis: Axis) -> bool {
+        match axis {
+            Axis::Horizontal => self.horizontal,
+            Axis::Vertical => self.vertical,
+            Axis::Rotational => self.rotational,
+            Axis::Full => self.horizontal && self.vertical,
+            Axis::Any => self.horizontal || self.vertical || self.rotational,
+        }
+    }
+}
+
+/// Compares the image against its mirrored and rotated self, allowing for small color differences.
 pub fn check_symmetry(img: &DynamicImage) -> Symmetry {
     let vertical = check_vertical_symmetry(img);
     let horizontal = check_horizontal_symmetry(img);
+    let rotational = check_rotational_symmetry(img);
     Symmetry {
         horizontal,
         vertical,
+        rotational,
     }
 }
 
+/// How close a flag comes to each symmetry, as the share of pixels that match their mirrored
+/// (or rotated) counterpart within the color tolerance. 1.0 is a perfect match.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SymmetryScores {
+    pub horizontal: f32,
+    pub vertical: f32,
+    pub rotational: f32,
+}
+
+pub fn symmetry_scores(img: &DynamicImage) -> SymmetryScores {
+    SymmetryScores {
+        horizontal: matching_share(img, &img.flipv()),
+        vertical: matching_share(img, &img.fliph()),
+        rotational: matching_share(img, &img.rotate180()),
+    }
+}
+
+/// The flag in grey with the pixels that break a symmetry in red, darker the more of the
+/// horizontal, vertical and rotational symmetries they break.
+pub fn asymmetry_heatmap(img: &DynamicImage) -> RgbaImage {
+    let transformed = [img.flipv(), img.fliph(), img.rotate180()];
+    let (width, height) = img.dimensions();
+    RgbaImage::from_fn(width, height, |x, y| {
+        let pixel = img.get_pixel(x, y);
+        let broken = transformed
+            .iter()
+            .filter(|other| !compare_pixel(pixel, other.get_pixel(x, y)))
+            .count();
+        let [r, g, b, _] = pixel.0;
+        // faded grey keeps the flag recognisable without competing with the red
+        let grey = 192 + ((r as u32 + g as u32 + b as u32) / 3 / 4) as u8;
+        match broken {
+            0 => Rgba([grey, grey, grey, 255]),
+            _ => Rgba([255, 160 - 50 * broken as u8, 160 - 50 * broken as u8, 255]),
+        }
+    })
+}
+
+fn matching_share(img: &DynamicImage, transformed: &DynamicImage) -> f32 {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return 1.0;
+    }
+    let matching = img
+        .pixels()
+        .filter(|(x, y, pixel)| compare_pixel(*pixel, transformed.get_pixel(*x, *y)))
+        .count();
+    matching as f32 / (width * height) as f32
+}
+
 fn check_vertical_symmetry(img: &DynamicImage) -> bool {
     // symmetrical if the left half is the same as the right half (think "A")
     let (width, height) = img.dimensions();
@@ -27,8 +134,8 @@ fn check_vertical_symmetry(img: &DynamicImage) -> bool {
     let half_width = width / 2;
     let bump: u32 = if width % 2 == 0 { 0 } else { 1 }; // bump the window by 1 if it's odd since u32 rounds down
 
-    let left_window = crop_image(&img, 0, 0, half_width, height);
-    let right_window = crop_image(&img, half_width + bump, 0, half_width, height).fliph(); // flip the right window to match the left
+    let left_window = crop_image(img, 0, 0, half_width, height);
+    let right_window = crop_image(img, half_width + bump, 0, half_width, height).fliph(); // flip the right window to match the left
 
     for y in 0..height {
         for x in 0..half_width {
@@ -72,8 +179,8 @@ fn check_horizontal_symmetry(img: &DynamicImage) -> bool {
     let half_height = height / 2;
     let bump: u32 = if height % 2 == 0 { 0 } else { 1 }; // bump the window by 1 if it's odd since u32 rounds down
 
-    let top_window = crop_image(&img, 0, 0, width, half_height);
-    let bottom_window = crop_image(&img, 0, half_height + bump, width, half_height).flipv(); // flip the bottom window to match the top
+    let top_window = crop_image(img, 0, 0, width, half_height);
+    let bottom_window = crop_image(img, 0, half_height + bump, width, half_height).flipv(); // flip the bottom window to match the top
 
     for x in 0..width {
         for y in 0..half_height {
@@ -110,6 +217,46 @@ fn check_horizontal_symmetry(img: &DynamicImage) -> bool {
     true
 }
 
+fn check_rotational_symmetry(img: &DynamicImage) -> bool {
+    // symmetrical if the image looks the same after a half turn (think "S")
+    let (width, height) = img.dimensions();
+    let rotated = img.rotate180();
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = img.get_pixel(x, y);
+            let rotated_pixel = rotated.get_pixel(x, y);
+
+            if !compare_pixel(pixel, rotated_pixel) {
+                if check_near_edge(x, y, width, height) {
+                    continue;
+                }
+
+                let small = crop_image(
+                    img,
+                    x - (FUZZY_WINDOW_DIM as u32) / 2,
+                    y - (FUZZY_WINDOW_DIM as u32) / 2,
+                    FUZZY_WINDOW_DIM as u32,
+                    FUZZY_WINDOW_DIM as u32,
+                );
+
+                let small_rotated = crop_image(
+                    &rotated,
+                    x - (FUZZY_WINDOW_DIM as u32) / 2,
+                    y - (FUZZY_WINDOW_DIM as u32) / 2,
+                    FUZZY_WINDOW_DIM as u32,
+                    FUZZY_WINDOW_DIM as u32,
+                );
+
+                if !fuzzy_compare(small, small_rotated) {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
 fn compare_pixel(a: Rgba<u8>, b: Rgba<u8>) -> bool {
     // Compare two pixels, returning true if they are within a certain tolerance of each other.
     // Sum up the values of the RGB channels and compare them. If they are within the tolerance % of each other, return true.
@@ -179,9 +326,117 @@ fn crop_image(img: &DynamicImage, x: u32, y: u32, width: u32, height: u32) -> Dy
 }
 
 fn check_near_edge(x: u32, y: u32, width: u32, height: u32) -> bool {
-    let dis_to_edge = FUZZY_WINDOW_DIM as i16 / 2;
-    (y as i16) - dis_to_edge / 2 <= 0
-        || (y as i16) + dis_to_edge >= height as i16
-        || (x as i16) - dis_to_edge <= 0
-        || (x as i16) + dis_to_edge >= width as i16
+    // i64 so flags 32768 px or more across don't overflow
+    let dis_to_edge = FUZZY_WINDOW_DIM as i64 / 2;
+    (y as i64) - dis_to_edge / 2 <= 0
+        || (y as i64) + dis_to_edge >= height as i64
+        || (x as i64) - dis_to_edge <= 0
+        || (x as i64) + dis_to_edge >= width as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    #[test]
+    fn very_wide_images_dont_overflow() {
+        // red over white, like a very long Monaco
+        let banner = RgbaImage::from_fn(40_000, 8, |_, y| if y < 4 { RED } else { WHITE });
+
+        let symmetry = check_symmetry(&DynamicImage::ImageRgba8(banner));
+
+        assert_eq!(
+            symmetry,
+            Symmetry {
+                horizontal: false,
+                vertical: true,
+                rotational: false
+            }
+        );
+    }
+
+    fn flag(pixel: impl Fn(u32, u32) -> Rgba<u8>) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(30, 20, pixel))
+    }
+
+    // red in the top left and bottom right quarters, the same after a half turn but not mirrored
+    fn quarters() -> DynamicImage {
+        flag(|x, y| if (x < 15) == (y < 10) { RED } else { WHITE })
+    }
+
+    // red over white, like Monaco
+    fn bicolour() -> DynamicImage {
+        flag(|_, y| if y < 10 { RED } else { WHITE })
+    }
+
+    // a red canton in the top left only
+    fn canton() -> DynamicImage {
+        flag(|x, y| if x < 15 && y < 10 { RED } else { WHITE })
+    }
+
+    #[test]
+    fn rotational_symmetry_without_lines() {
+        assert_eq!(
+            check_symmetry(&quarters()),
+            Symmetry {
+                horizontal: false,
+                vertical: false,
+                rotational: true
+            }
+        );
+        assert_eq!(
+            check_symmetry(&bicolour()),
+            Symmetry {
+                horizontal: false,
+                vertical: true,
+                rotational: false
+            }
+        );
+        assert_eq!(
+            check_symmetry(&canton()),
+            Symmetry {
+                horizontal: false,
+                vertical: false,
+                rotational: false
+            }
+        );
+    }
+
+    #[test]
+    fn scores_are_the_share_of_matching_pixels() {
+        let scores = |img: DynamicImage| {
+            let scores = symmetry_scores(&img);
+            [scores.horizontal, scores.vertical, scores.rotational]
+        };
+
+        assert_eq!(scores(flag(|_, _| RED)), [1.0, 1.0, 1.0]);
+        assert_eq!(scores(quarters()), [0.0, 0.0, 1.0]);
+        assert_eq!(scores(bicolour()), [0.0, 1.0, 0.0]);
+        assert_eq!(scores(canton()), [0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn heatmap_marks_pixels_by_how_many_symmetries_they_break() {
+        let grey_red = Rgba([213, 213, 213, 255]);
+
+        let heatmap = asymmetry_heatmap(&bicolour());
+        assert_eq!(heatmap.dimensions(), (30, 20));
+        // every pixel breaks the horizontal line and the half turn
+        assert!(heatmap
+            .pixels()
+            .all(|pixel| *pixel == Rgba([255, 60, 60, 255])));
+
+        let heatmap = asymmetry_heatmap(&canton());
+        // the canton breaks all three, each other quarter only the one that maps it onto the canton
+        assert_eq!(*heatmap.get_pixel(0, 0), Rgba([255, 10, 10, 255]));
+        for (x, y) in [(29, 0), (0, 19), (29, 19)] {
+            assert_eq!(*heatmap.get_pixel(x, y), Rgba([255, 110, 110, 255]));
+        }
+
+        let heatmap = asymmetry_heatmap(&flag(|_, _| RED));
+        assert!(heatmap.pixels().all(|pixel| *pixel == grey_red));
+    }
 }
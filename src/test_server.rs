@@ -0,0 +1,164 @@
+//! Local stand-in for the flag CDN so the fetcher can be tested end to end without network access.
+use image::{ImageFormat, Rgba, RgbaImage};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::url_template::UrlTemplate;
+
+#[derive(Clone)]
+pub struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    delay: Duration,
+}
+
+impl Reply {
+    pub fn new(status: u16, content_type: &str, body: Vec<u8>) -> Reply {
+        Reply {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body,
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn png(img: &RgbaImage) -> Reply {
+        let mut body = Vec::new();
+        img.write_to(&mut Cursor::new(&mut body), ImageFormat::Png)
+            .unwrap();
+        Reply::new(200, "image/png", body)
+    }
+
+    pub fn status(status: u16) -> Reply {
+        Reply::new(
+            status,
+            "text/plain",
+            format!("status {}", status).into_bytes(),
+        )
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Reply {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Wait before sending anything, to trip the client's timeouts.
+    pub fn delayed(mut self, delay: Duration) -> Reply {
+        self.delay = delay;
+        self
+    }
+}
+
+/// A flag image split into a `left` and `right` colored half.
+pub fn fixture_flag(left: [u8; 3], right: [u8; 3]) -> RgbaImage {
+    RgbaImage::from_fn(12, 8, |x, _| {
+        let [r, g, b] = if x < 6 { left } else { right };
+        Rgba([r, g, b, 255])
+    })
+}
+
+type Routes = HashMap<String, Vec<Reply>>;
+
+/// Serves a scripted sequence of replies per path: each request takes the next reply and the last one repeats.
+pub struct TestServer {
+    port: u16,
+    routes: Arc<Mutex<Routes>>,
+    hits: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl TestServer {
+    pub async fn start() -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let routes: Arc<Mutex<Routes>> = Arc::default();
+        let hits: Arc<Mutex<HashMap<String, usize>>> = Arc::default();
+
+        let (server_routes, server_hits) = (routes.clone(), hits.clone());
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let (routes, hits) = (server_routes.clone(), server_hits.clone());
+                tokio::spawn(async move { handle(stream, routes, hits).await });
+            }
+        });
+
+        TestServer { port, routes, hits }
+    }
+
+    pub fn route(&self, path: &str, replies: Vec<Reply>) {
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), replies);
+    }
+
+    pub fn hits(&self, path: &str) -> usize {
+        *self.hits.lock().unwrap().get(path).unwrap_or(&0)
+    }
+
+    /// Template matching the flagcdn layout, e.g. `/h80/fr.png`.
+    pub fn url_template(&self) -> UrlTemplate {
+        UrlTemplate::parse(&format!(
+            "http://127.0.0.1:{}/{{size}}/{{code}}.{{ext}}",
+            self.port
+        ))
+        .unwrap()
+    }
+
+    pub fn svg_url_template(&self) -> UrlTemplate {
+        UrlTemplate::parse(&format!("http://127.0.0.1:{}/{{code}}.svg", self.port)).unwrap()
+    }
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    routes: Arc<Mutex<Routes>>,
+    hits: Arc<Mutex<HashMap<String, usize>>>,
+) {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    // request line looks like "GET /h80/fr.png HTTP/1.1"
+    let request = String::from_utf8_lossy(&request);
+    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+    let reply = {
+        let mut hits = hits.lock().unwrap();
+        let count = hits.entry(path.clone()).or_insert(0);
+        *count += 1;
+
+        let routes = routes.lock().unwrap();
+        match routes.get(&path) {
+            Some(replies) => replies[(*count - 1).min(replies.len() - 1)].clone(),
+            None => Reply::status(404),
+        }
+    };
+
+    tokio::time::sleep(reply.delay).await;
+
+    let mut response = format!("HTTP/1.1 {} Test\r\n", reply.status);
+    for (name, value) in &reply.headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        reply.body.len()
+    ));
+
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.write_all(&reply.body).await;
+    let _ = stream.shutdown().await;
+}
@@ -0,0 +1,278 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use flag_symmetry::consts::countries;
+use flag_symmetry::flags::{FlagEntry, FlagSource, FALLBACK_EMOJI};
+
+// one row of a user dataset, as read from CSV or JSON
+#[derive(Debug, Deserialize)]
+struct DatasetRow {
+    id: String,
+    name: String,
+    #[serde(default, alias = "label")]
+    emoji: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    path: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct RowError {
+    pub row: usize, // 1-based: CSV line number or JSON array position
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum DatasetError {
+    Read(String),
+    Rows(Vec<RowError>),
+}
+
+impl fmt::Display for DatasetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatasetError::Read(message) => write!(f, "failed to read dataset: {}", message),
+            DatasetError::Rows(errors) => {
+                write!(f, "dataset has {} invalid rows:", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  row {}: {}", error.row, error.message)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for DatasetError {}
+
+/// Loads a user dataset from a `.csv` or `.json` file.
+///
+/// Each row has an `id`, a display `name`, an optional `emoji` (or `label`) and either a `url` or a
+/// `path` to the image. Relative paths are resolved against the dataset file. Rows with neither are
+/// fetched like built-in flags, so their id has to be a known code.
+/// `existing` are ids the rows may not reuse, e.g. the built-in flags when extending them.
+pub fn load_dataset(path: &Path, existing: &[FlagEntry]) -> Result<Vec<FlagEntry>, DatasetError> {
+    let rows = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => read_json(path)?,
+        Some("csv") => read_csv(path)?,
+        _ => {
+            return Err(DatasetError::Read(format!(
+                "{} should be a .csv or .json file",
+                path.display()
+            )))
+        }
+    };
+
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let mut seen: HashSet<String> = existing
+        .iter()
+        .map(|entry| entry.id.to_lowercase())
+        .collect();
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+
+    for (row, parsed) in rows {
+        let result = parsed.and_then(|data| validate_row(data, base_dir, &mut seen));
+        match result {
+            Ok(entry) => entries.push(entry),
+            Err(message) => errors.push(RowError { row, message }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(entries)
+    } else {
+        Err(DatasetError::Rows(errors))
+    }
+}
+
+type ParsedRows = Vec<(usize, Result<DatasetRow, String>)>;
+
+fn read_csv(path: &Path) -> Result<ParsedRows, DatasetError> {
+    let mut reader =
+        csv::Reader::from_path(path).map_err(|err| DatasetError::Read(err.to_string()))?;
+    Ok(reader
+        .deserialize::<DatasetRow>()
+        .enumerate()
+        .map(|(i, result)| match result {
+            Ok(row) => (i + 2, Ok(row)), // +1 for the header, +1 for 1-based lines
+            Err(err) => {
+                let line = err.position().map_or(i + 2, |p| p.line() as usize);
+                (line, Err(err.to_string()))
+            }
+        })
+        .collect())
+}
+
+fn read_json(path: &Path) -> Result<ParsedRows, DatasetError> {
+    // parse rows one by one so a bad row doesn't hide the errors in the others
+    let text = std::fs::read_to_string(path).map_err(|err| DatasetError::Read(err.to_string()))?;
+    let values: Vec<serde_json::Value> =
+        serde_json::from_str(&text).map_err(|err| DatasetError::Read(err.to_string()))?;
+    Ok(values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| {
+            (
+                i + 1,
+                serde_json::from_value(value).map_err(|err| err.to_string()),
+            )
+        })
+        .collect())
+}
+
+fn validate_row(
+    row: DatasetRow,
+    base_dir: &Path,
+    seen: &mut HashSet<String>,
+) -> Result<FlagEntry, String> {
+    let id = row.id.trim().to_string();
+    if id.is_empty() {
+        return Err("id is empty".to_string());
+    }
+    if row.name.trim().is_empty() {
+        return Err(format!("name is empty for '{}'", id));
+    }
+    // ids are looked up case-insensitively, "FR" would shadow the built-in "fr"
+    if !seen.insert(id.to_lowercase()) {
+        return Err(format!("duplicate id '{}'", id));
+    }
+
+    let url = row.url.filter(|url| !url.trim().is_empty());
+    let path = row.path.filter(|path| !path.trim().is_empty());
+    let country = countries::lookup(&id.to_lowercase());
+
+    let source = match (url, path) {
+        (Some(_), Some(_)) => return Err(format!("'{}' has both a url and a path", id)),
+        (Some(url), None) => {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!(
+                    "url '{}' should start with http:// or https://",
+                    url
+                ));
+            }
+            FlagSource::Url(url)
+        }
+        (None, Some(path)) => {
+            let path = base_dir.join(PathBuf::from(path));
+            if !path.is_file() {
+                return Err(format!("path '{}' doesn't exist", path.display()));
+            }
+            FlagSource::Path(path)
+        }
+        (None, None) if country.is_some() => FlagSource::Registry,
+        (None, None) => return Err(format!("'{}' needs a url or a path", id)),
+    };
+
+    Ok(FlagEntry {
+        id,
+        name: row.name.trim().to_string(),
+        emoji: row
+            .emoji
+            .filter(|emoji| !emoji.trim().is_empty())
+            .unwrap_or_else(|| FALLBACK_EMOJI.to_string()),
+        source,
+        country,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::{tempdir, TempDir};
+
+    fn dataset_file(dir: &TempDir, file_name: &str, contents: &str) -> PathBuf {
+        let path = dir.path().join(file_name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn row_errors(err: DatasetError) -> Vec<(usize, String)> {
+        match err {
+            DatasetError::Rows(errors) => errors.into_iter().map(|e| (e.row, e.message)).collect(),
+            DatasetError::Read(message) => panic!("expected row errors, got {}", message),
+        }
+    }
+
+    #[test]
+    fn csv_and_json_load_the_same_entries() {
+        let dir = tempdir().unwrap();
+        let csv = dataset_file(
+            &dir,
+            "flags.csv",
+            "id,name,emoji,url\nacme,Acme Corp,🏭,https://example.com/acme.png\nfr,France,,\n",
+        );
+        let json = dataset_file(
+            &dir,
+            "flags.json",
+            r#"[{"id": "acme", "name": "Acme Corp", "label": "🏭", "url": "https://example.com/acme.png"},
+                {"id": "fr", "name": "France"}]"#,
+        );
+
+        let from_csv = load_dataset(&csv, &[]).unwrap();
+        let from_json = load_dataset(&json, &[]).unwrap();
+
+        assert_eq!(format!("{:?}", from_csv), format!("{:?}", from_json));
+        assert!(matches!(&from_csv[0].source, FlagSource::Url(url) if url.ends_with("acme.png")));
+        assert!(matches!(from_csv[1].source, FlagSource::Registry));
+        assert_eq!(from_csv[1].emoji, FALLBACK_EMOJI);
+    }
+
+    #[test]
+    fn missing_columns_are_reported_per_row() {
+        let dir = tempdir().unwrap();
+        let csv = dataset_file(
+            &dir,
+            "flags.csv",
+            "id,url\nacme,https://example.com/a.png\n",
+        );
+
+        let errors = row_errors(load_dataset(&csv, &[]).unwrap_err());
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 2);
+        assert!(errors[0].1.contains("name"), "{}", errors[0].1);
+    }
+
+    #[test]
+    fn rejects_urls_without_a_scheme() {
+        let dir = tempdir().unwrap();
+        let json = dataset_file(
+            &dir,
+            "flags.json",
+            r#"[{"id": "acme", "name": "Acme", "url": "example.com/acme.png"}]"#,
+        );
+
+        let errors = row_errors(load_dataset(&json, &[]).unwrap_err());
+
+        assert_eq!(
+            errors,
+            vec![(
+                1,
+                "url 'example.com/acme.png' should start with http:// or https://".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn duplicate_ids_ignore_case() {
+        let dir = tempdir().unwrap();
+        let csv = dataset_file(&dir, "flags.csv",
+            "id,name,url\nacme,Acme,https://example.com/a.png\nACME,Acme again,https://example.com/b.png\nFR,France,https://example.com/fr.png\n",
+        );
+        let existing = [FlagEntry::from_country(countries::lookup("fr").unwrap())];
+
+        let errors = row_errors(load_dataset(&csv, &existing).unwrap_err());
+
+        assert_eq!(
+            errors,
+            vec![
+                (3, "duplicate id 'ACME'".to_string()),
+                (4, "duplicate id 'FR'".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use crate::symmetry::Symmetry;
+use usvg::tiny_skia_path::{PathSegment, Point};
+use usvg::{Group, Node, Paint, Transform, Tree};
+
+const GEOMETRY_TOLERANCE: f32 = 0.005; // max distance between matching points, as a fraction of the flag's dimensions
+
+#[derive(Debug, PartialEq)]
+enum ShapePaint {
+    Color([u8; 4]),
+    Gradient,
+    Pattern,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PathPoint {
+    point: Point,
+    anchor: bool, // false for bezier control points
+}
+
+#[derive(Debug)]
+struct Shape {
+    paint: ShapePaint,
+    subpaths: Vec<Vec<PathPoint>>, // in drawing order, normalized to the unit square
+    bounds: (Point, Point),        // min and max corner
+}
+
+pub fn check_svg_symmetry(data: &[u8]) -> Result<Symmetry, usvg::Error> {
+    let tree = Tree::from_data(data, &usvg::Options::default())?;
+    let shapes = collect_shapes(&tree);
+
+    // mirror across the vertical axis (think "A")
+    let vertical = check_invariance(&shapes, |p| Point::from_xy(1.0 - p.x, p.y));
+    // mirror across the horizontal axis (think "D")
+    let horizontal = check_invariance(&shapes, |p| Point::from_xy(p.x, 1.0 - p.y));
+    // rotate 180 degrees around the center (think "S")
+    let rotational = check_invariance(&shapes, |p| Point::from_xy(1.0 - p.x, 1.0 - p.y));
+
+    Ok(Symmetry {
+        horizontal,
+        vertical,
+        rotational,
+    })
+}
+
+fn collect_shapes(tree: &Tree) -> Vec<Shape> {
+    // Normalize every filled path into the unit square so the result doesn't depend on the viewBox or any nested transforms.
+    let size = tree.size();
+    let normalize = Transform::from_scale(1.0 / size.width(), 1.0 / size.height());
+
+    let mut shapes = Vec::new();
+    collect_group_shapes(tree.root(), normalize, &mut shapes);
+    shapes
+}
+
+fn collect_group_shapes(group: &Group, normalize: Transform, shapes: &mut Vec<Shape>) {
+    for node in group.children() {
+        match node {
+            Node::Group(child) => collect_group_shapes(child, normalize, shapes),
+            Node::Path(path) => {
+                if !path.is_visible() {
+                    continue;
+                }
+                let Some(fill) = path.fill() else {
+                    continue; // strokes alone don't count as filled shapes
+                };
+                let transform = path.abs_transform().post_concat(normalize);
+                let Some(data) = path.data().clone().transform(transform) else {
+                    continue;
+                };
+
+                let paint = match fill.paint() {
+                    Paint::Color(c) => {
+                        ShapePaint::Color([c.red, c.green, c.blue, fill.opacity().to_u8()])
+                    }
+                    Paint::LinearGradient(_) | Paint::RadialGradient(_) => ShapePaint::Gradient,
+                    Paint::Pattern(_) => ShapePaint::Pattern,
+                };
+
+                let bounds = data.bounds();
+                shapes.push(Shape {
+                    paint,
+                    subpaths: subpaths(&data),
+                    bounds: (
+                        Point::from_xy(bounds.left(), bounds.top()),
+                        Point::from_xy(bounds.right(), bounds.bottom()),
+                    ),
+                });
+            }
+            // embedded images and text are very rare in flags, so they're ignored
+            Node::Image(_) | Node::Text(_) => {}
+        }
+    }
+}
+
+fn subpaths(data: &usvg::tiny_skia_path::Path) -> Vec<Vec<PathPoint>> {
+    // A mirror or rotation maps bezier control points onto control points, so comparing them is exact for curves too.
+    let mut subpaths: Vec<Vec<PathPoint>> = Vec::new();
+    for segment in data.segments() {
+        let anchor = |point| PathPoint {
+            point,
+            anchor: true,
+        };
+        let control = |point| PathPoint {
+            point,
+            anchor: false,
+        };
+        match segment {
+            PathSegment::MoveTo(p) => subpaths.push(vec![anchor(p)]),
+            PathSegment::LineTo(p) => subpaths.last_mut().unwrap().push(anchor(p)),
+            PathSegment::QuadTo(p1, p2) => subpaths
+                .last_mut()
+                .unwrap()
+                .extend([control(p1), anchor(p2)]),
+            PathSegment::CubicTo(p1, p2, p3) => {
+                subpaths
+                    .last_mut()
+                    .unwrap()
+                    .extend([control(p1), control(p2), anchor(p3)])
+            }
+            PathSegment::Close => {}
+        }
+    }
+
+    // fills are always closed, so a final point back on the start is the same vertex twice
+    for subpath in &mut subpaths {
+        if subpath.len() > 1 {
+            let (first, last) = (subpath[0], subpath[subpath.len() - 1]);
+            if last.anchor && close(first.point, last.point) {
+                subpath.pop();
+            }
+        }
+    }
+    subpaths
+}
+
+fn check_invariance<F: Fn(Point) -> Point>(shapes: &[Shape], transform: F) -> bool {
+    // The flag is symmetric if every transformed shape lands on an (unused) original shape with the same paint,
+    // and shapes that overlap are still painted in the same order.
+    let mut matched = vec![false; shapes.len()];
+    let mut targets = Vec::with_capacity(shapes.len());
+
+    for shape in shapes {
+        let transformed: Vec<Vec<PathPoint>> = shape
+            .subpaths
+            .iter()
+            .map(|subpath| {
+                subpath
+                    .iter()
+                    .map(|p| PathPoint {
+                        point: transform(p.point),
+                        anchor: p.anchor,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let candidate = shapes.iter().enumerate().position(|(i, other)| {
+            !matched[i]
+                && other.paint == shape.paint
+                && same_subpaths(&transformed, &other.subpaths)
+        });
+
+        match candidate {
+            Some(i) => {
+                matched[i] = true;
+                targets.push(i);
+            }
+            None => return false,
+        }
+    }
+
+    paint_order_kept(shapes, &targets)
+}
+
+fn paint_order_kept(shapes: &[Shape], targets: &[usize]) -> bool {
+    // Two overlapping shapes of different colors whose images are painted the other way round
+    // would look different, e.g. a blue square over a red one mirrored onto a red over a blue.
+    for i in 0..shapes.len() {
+        for j in i + 1..shapes.len() {
+            if targets[i] > targets[j]
+                && shapes[i].paint != shapes[j].paint
+                && overlap(&shapes[i], &shapes[j])
+            {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn overlap(a: &Shape, b: &Shape) -> bool {
+    // bounding boxes, so shapes that only touch along an edge don't count
+    let ((a_min, a_max), (b_min, b_max)) = (a.bounds, b.bounds);
+    a_max.x.min(b_max.x) - a_min.x.max(b_min.x) > GEOMETRY_TOLERANCE
+        && a_max.y.min(b_max.y) - a_min.y.max(b_min.y) > GEOMETRY_TOLERANCE
+}
+
+fn same_subpaths(a: &[Vec<PathPoint>], b: &[Vec<PathPoint>]) -> bool {
+    // subpaths, e.g. the stars of one path, may come in any order but each has to be used once
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut used = vec![false; b.len()];
+    a.iter().all(|subpath| {
+        let found = b
+            .iter()
+            .enumerate()
+            .position(|(i, other)| !used[i] && same_cycle(subpath, other));
+        match found {
+            Some(i) => {
+                used[i] = true;
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+fn same_cycle(a: &[PathPoint], b: &[PathPoint]) -> bool {
+    // Once mirrored, a closed outline may start at a different vertex and run in the opposite
+    // direction, so try every anchor of `b` as the start, going forwards and backwards.
+    let n = a.len();
+    if n != b.len() {
+        return false;
+    }
+    let matches = |i: usize, j: usize| a[i].anchor == b[j].anchor && close(a[i].point, b[j].point);
+    (0..n).filter(|&start| b[start].anchor).any(|start| {
+        (0..n).all(|i| matches(i, (start + i) % n))
+            || (0..n).all(|i| matches(i, (start + n - i) % n))
+    })
+}
+
+fn close(a: Point, b: Point) -> bool {
+    (a.x - b.x).abs() <= GEOMETRY_TOLERANCE && (a.y - b.y).abs() <= GEOMETRY_TOLERANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn svg(body: &str) -> Vec<u8> {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="60">{}</svg>"#,
+            body
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn symmetric_drawing() {
+        let japan = svg(r##"<rect width="100" height="60" fill="#fff"/>
+            <circle cx="50" cy="30" r="18" fill="#bc002d"/>"##);
+
+        let symmetry = check_svg_symmetry(&japan).unwrap();
+
+        assert!(symmetry.horizontal && symmetry.vertical && symmetry.rotational);
+    }
+
+    #[test]
+    fn asymmetric_drawing() {
+        let bicolour = svg(r##"<rect width="50" height="60" fill="#f00"/>
+            <rect x="50" width="50" height="60" fill="#00f"/>"##);
+
+        let symmetry = check_svg_symmetry(&bicolour).unwrap();
+
+        assert!(symmetry.horizontal);
+        assert!(!symmetry.vertical && !symmetry.rotational);
+    }
+
+    #[test]
+    fn point_order_matters() {
+        // the same corners as its mirror image, but the notch is only on the right
+        let notched = svg(r##"<path d="M0 0 L100 0 L50 30 L100 60 L0 60 Z" fill="#f00"/>"##);
+
+        let symmetry = check_svg_symmetry(&notched).unwrap();
+
+        assert!(symmetry.horizontal);
+        assert!(!symmetry.vertical);
+    }
+
+    #[test]
+    fn paint_order_matters() {
+        // red and blue squares on both sides, blue on top on the left and red on top on the right
+        let swapped = svg(r##"<rect width="40" height="60" fill="#f00"/>
+            <rect width="40" height="60" fill="#00f"/>
+            <rect x="60" width="40" height="60" fill="#00f"/>
+            <rect x="60" width="40" height="60" fill="#f00"/>"##);
+        let same = svg(r##"<rect width="40" height="60" fill="#f00"/>
+            <rect width="40" height="60" fill="#00f"/>
+            <rect x="60" width="40" height="60" fill="#f00"/>
+            <rect x="60" width="40" height="60" fill="#00f"/>"##);
+
+        assert!(!check_svg_symmetry(&swapped).unwrap().vertical);
+        assert!(check_svg_symmetry(&same).unwrap().vertical);
+    }
+}